@@ -0,0 +1,681 @@
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::Arc,
+};
+
+use crate::{Headers, ResponseHandler};
+
+//
+pub trait ResponseHandlerExt: ResponseHandler + Sized {
+    fn or_else<B>(self, other: B) -> OrElse<Self, B>
+    where
+        B: ResponseHandler,
+    {
+        OrElse {
+            first: self,
+            second: other,
+        }
+    }
+
+    fn size_limit(self, max_size: usize) -> SizeLimit<Self> {
+        SizeLimit::new(self, max_size)
+    }
+}
+
+impl<H> ResponseHandlerExt for H where H: ResponseHandler {}
+
+//
+trait ErasedResponseHandler: Send + Sync {
+    fn clone_box(&self) -> Box<dyn ErasedResponseHandler>;
+
+    fn dyn_name(&self) -> Option<&str>;
+
+    fn dyn_try_make_static_response_bytes(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+    ) -> Result<Option<Vec<u8>>, IoError>;
+
+    fn dyn_reset_element(&mut self);
+
+    fn dyn_parse_new_response_bytes(
+        &mut self,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Result<Option<usize>, IoError>;
+
+    fn dyn_make_request_bytes_with_headers(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+        headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError>;
+
+    fn dyn_parse_response_headers(&mut self, response_bytes: &[u8]) -> Result<Headers, IoError>;
+}
+
+impl<H> ErasedResponseHandler for H
+where
+    H: ResponseHandler + Send + Sync + 'static,
+{
+    fn clone_box(&self) -> Box<dyn ErasedResponseHandler> {
+        Box::new(self.clone())
+    }
+
+    fn dyn_name(&self) -> Option<&str> {
+        self.name()
+    }
+
+    fn dyn_try_make_static_response_bytes(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        self.try_make_static_response_bytes(service_name, fn_name, request_bytes)
+    }
+
+    fn dyn_reset_element(&mut self) {
+        self.reset_element()
+    }
+
+    fn dyn_parse_new_response_bytes(
+        &mut self,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Result<Option<usize>, IoError> {
+        self.parse_new_response_bytes(response_bytes, new_bytes_from)
+    }
+
+    fn dyn_make_request_bytes_with_headers(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+        headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        self.make_request_bytes_with_headers(service_name, fn_name, request_bytes, headers)
+    }
+
+    fn dyn_parse_response_headers(&mut self, response_bytes: &[u8]) -> Result<Headers, IoError> {
+        self.parse_response_headers(response_bytes)
+    }
+}
+
+struct BoxResponseHandler(Box<dyn ErasedResponseHandler>);
+
+impl Clone for BoxResponseHandler {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+
+type Routes = HashMap<(Vec<u8>, Vec<u8>), BoxResponseHandler>;
+
+//
+// Dispatches per (service_name, fn_name). The handler is selected in
+// `try_make_static_response_bytes`, which the transport calls first on every call.
+#[derive(Clone)]
+pub struct Router {
+    routes: Arc<Routes>,
+    fallback: Arc<BoxResponseHandler>,
+    selected: Option<BoxResponseHandler>,
+}
+
+impl core::fmt::Debug for Router {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Router")
+            .field("routes", &self.routes.len())
+            .field("fallback", &self.fallback.0.dyn_name())
+            .field(
+                "selected",
+                &self.selected.as_ref().map(|handler| handler.0.dyn_name()),
+            )
+            .finish()
+    }
+}
+
+impl Router {
+    pub fn new<H>(fallback: H) -> Self
+    where
+        H: ResponseHandler + Send + Sync + 'static,
+    {
+        Self {
+            routes: Arc::new(HashMap::new()),
+            fallback: Arc::new(BoxResponseHandler(Box::new(fallback))),
+            selected: None,
+        }
+    }
+
+    pub fn route<H>(mut self, service_name: &str, fn_name: &str, handler: H) -> Self
+    where
+        H: ResponseHandler + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.routes).insert(
+            (service_name.into(), fn_name.into()),
+            BoxResponseHandler(Box::new(handler)),
+        );
+        self
+    }
+
+    fn select(&self, service_name: &[u8], fn_name: &[u8]) -> BoxResponseHandler {
+        self.routes
+            .get(&(service_name.to_vec(), fn_name.to_vec()))
+            .unwrap_or(&self.fallback)
+            .clone()
+    }
+
+    fn selected_or_fallback(&mut self) -> &mut dyn ErasedResponseHandler {
+        let fallback = &self.fallback;
+        self.selected
+            .get_or_insert_with(|| BoxResponseHandler::clone(fallback))
+            .0
+            .as_mut()
+    }
+}
+
+impl ResponseHandler for Router {
+    fn name(&self) -> Option<&str> {
+        Some("Router")
+    }
+
+    fn try_make_static_response_bytes(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        self.selected
+            .insert(self.select(service_name, fn_name))
+            .0
+            .dyn_try_make_static_response_bytes(service_name, fn_name, request_bytes)
+    }
+
+    fn parse_response_bytes(&mut self, response_bytes: &[u8]) -> Result<Option<usize>, IoError> {
+        self.parse_new_response_bytes(response_bytes, 0)
+    }
+
+    fn reset(&mut self) {
+        self.selected = None;
+    }
+
+    // The route stays selected for the whole call.
+    fn reset_element(&mut self) {
+        if let Some(selected) = &mut self.selected {
+            selected.0.dyn_reset_element();
+        }
+    }
+
+    fn parse_new_response_bytes(
+        &mut self,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Result<Option<usize>, IoError> {
+        self.selected_or_fallback()
+            .dyn_parse_new_response_bytes(response_bytes, new_bytes_from)
+    }
+
+    // The request is written before the handler gets selected, so it is picked here as well.
+    fn make_request_bytes_with_headers(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+        headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        self.select(service_name, fn_name)
+            .0
+            .dyn_make_request_bytes_with_headers(service_name, fn_name, request_bytes, headers)
+    }
+
+    fn parse_response_headers(&mut self, response_bytes: &[u8]) -> Result<Headers, IoError> {
+        self.selected_or_fallback()
+            .dyn_parse_response_headers(response_bytes)
+    }
+}
+
+//
+// Parsing always goes to the first handler.
+#[derive(Debug, Clone)]
+pub struct OrElse<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> ResponseHandler for OrElse<A, B>
+where
+    A: ResponseHandler,
+    B: ResponseHandler,
+{
+    fn name(&self) -> Option<&str> {
+        self.first.name()
+    }
+
+    fn try_make_static_response_bytes(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        match self
+            .first
+            .try_make_static_response_bytes(service_name, fn_name, request_bytes)?
+        {
+            Some(bytes) => Ok(Some(bytes)),
+            None => {
+                self.second
+                    .try_make_static_response_bytes(service_name, fn_name, request_bytes)
+            }
+        }
+    }
+
+    fn parse_response_bytes(&mut self, response_bytes: &[u8]) -> Result<Option<usize>, IoError> {
+        self.first.parse_response_bytes(response_bytes)
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+
+    fn reset_element(&mut self) {
+        self.first.reset_element();
+        self.second.reset_element();
+    }
+
+    fn parse_new_response_bytes(
+        &mut self,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Result<Option<usize>, IoError> {
+        self.first
+            .parse_new_response_bytes(response_bytes, new_bytes_from)
+    }
+
+    fn make_request_bytes_with_headers(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+        headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        self.first
+            .make_request_bytes_with_headers(service_name, fn_name, request_bytes, headers)
+    }
+
+    fn parse_response_headers(&mut self, response_bytes: &[u8]) -> Result<Headers, IoError> {
+        self.first.parse_response_headers(response_bytes)
+    }
+}
+
+//
+#[derive(Debug, Clone)]
+pub struct SizeLimit<H> {
+    inner: H,
+    max_size: usize,
+}
+
+impl<H> SizeLimit<H> {
+    pub fn new(inner: H, max_size: usize) -> Self {
+        Self { inner, max_size }
+    }
+
+    pub fn get_max_size(&self) -> usize {
+        self.max_size
+    }
+
+    fn check(&self, size: usize) -> Result<(), IoError> {
+        if size > self.max_size {
+            return Err(IoError::new(
+                IoErrorKind::InvalidData,
+                format!("Response size {size} exceeds the limit {}", self.max_size),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<H> ResponseHandler for SizeLimit<H>
+where
+    H: ResponseHandler,
+{
+    fn name(&self) -> Option<&str> {
+        self.inner.name()
+    }
+
+    fn try_make_static_response_bytes(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        let bytes =
+            self.inner
+                .try_make_static_response_bytes(service_name, fn_name, request_bytes)?;
+        if let Some(bytes) = &bytes {
+            self.check(bytes.len())?;
+        }
+        Ok(bytes)
+    }
+
+    fn parse_response_bytes(&mut self, response_bytes: &[u8]) -> Result<Option<usize>, IoError> {
+        let parsed = self.inner.parse_response_bytes(response_bytes)?;
+        self.check_parsed(response_bytes, parsed)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn reset_element(&mut self) {
+        self.inner.reset_element();
+    }
+
+    fn parse_new_response_bytes(
+        &mut self,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Result<Option<usize>, IoError> {
+        let parsed = self
+            .inner
+            .parse_new_response_bytes(response_bytes, new_bytes_from)?;
+        self.check_parsed(response_bytes, parsed)
+    }
+
+    fn make_request_bytes_with_headers(
+        &mut self,
+        service_name: &'static [u8],
+        fn_name: &'static [u8],
+        request_bytes: &[u8],
+        headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        self.inner
+            .make_request_bytes_with_headers(service_name, fn_name, request_bytes, headers)
+    }
+
+    fn parse_response_headers(&mut self, response_bytes: &[u8]) -> Result<Headers, IoError> {
+        self.inner.parse_response_headers(response_bytes)
+    }
+}
+
+impl<H> SizeLimit<H> {
+    fn check_parsed(
+        &self,
+        response_bytes: &[u8],
+        parsed: Option<usize>,
+    ) -> Result<Option<usize>, IoError> {
+        match parsed {
+            Some(n) => {
+                self.check(n)?;
+                Ok(Some(n))
+            }
+            None => {
+                // Incomplete yet, so the response is larger than what was received.
+                self.check(response_bytes.len() + 1)?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::MockResponseHandler;
+
+    #[derive(Debug, Clone)]
+    struct StaticResponseHandler(&'static [u8]);
+
+    impl ResponseHandler for StaticResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(Some(self.0.to_vec()))
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            Ok((response_bytes.len() >= self.0.len()).then_some(self.0.len()))
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct FixedResponseHandler(usize);
+
+    impl ResponseHandler for FixedResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            Ok((response_bytes.len() >= self.0).then_some(self.0))
+        }
+    }
+
+    #[test]
+    fn test_router() -> Result<(), Box<dyn std::error::Error>> {
+        let router = Router::new(FixedResponseHandler(2))
+            .route("my_service", "authenticate", StaticResponseHandler(b"ok"))
+            .route("my_service", "execute", FixedResponseHandler(4));
+
+        let mut h = router.clone();
+        assert_eq!(
+            h.try_make_static_response_bytes(b"my_service", b"authenticate", b"")?,
+            Some(b"ok".to_vec())
+        );
+
+        let mut h = router.clone();
+        assert_eq!(
+            h.try_make_static_response_bytes(b"my_service", b"execute", b"")?,
+            None
+        );
+        assert_eq!(h.parse_response_bytes(b"foo")?, None);
+        assert_eq!(h.parse_response_bytes(b"foobar")?, Some(4));
+        // The next element of the same call.
+        h.reset_element();
+        assert_eq!(h.parse_response_bytes(b"foo")?, None);
+        assert_eq!(h.parse_response_bytes(b"foobar")?, Some(4));
+
+        let mut h = router.clone();
+        assert_eq!(
+            h.try_make_static_response_bytes(b"my_service", b"other", b"")?,
+            None
+        );
+        assert_eq!(h.parse_response_bytes(b"foo")?, Some(2));
+
+        println!("{router:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_router_headers() -> Result<(), Box<dyn std::error::Error>> {
+        // Headers go before the request as `k=v;`, the response ones are read back the same way.
+        #[derive(Debug, Clone)]
+        struct HeadersResponseHandler;
+
+        impl ResponseHandler for HeadersResponseHandler {
+            fn try_make_static_response_bytes(
+                &mut self,
+                _service_name: &'static [u8],
+                _fn_name: &'static [u8],
+                _request_bytes: &[u8],
+            ) -> Result<Option<Vec<u8>>, IoError> {
+                Ok(None)
+            }
+
+            fn parse_response_bytes(
+                &mut self,
+                response_bytes: &[u8],
+            ) -> Result<Option<usize>, IoError> {
+                Ok(Some(response_bytes.len()))
+            }
+
+            fn make_request_bytes_with_headers(
+                &mut self,
+                _service_name: &'static [u8],
+                _fn_name: &'static [u8],
+                request_bytes: &[u8],
+                headers: &Headers,
+            ) -> Result<Option<Vec<u8>>, IoError> {
+                let mut bytes = vec![];
+                for (k, v) in headers {
+                    bytes.extend_from_slice(format!("{k}={v};").as_bytes());
+                }
+                bytes.extend_from_slice(request_bytes);
+                Ok(Some(bytes))
+            }
+
+            fn parse_response_headers(
+                &mut self,
+                response_bytes: &[u8],
+            ) -> Result<Headers, IoError> {
+                Ok(String::from_utf8_lossy(response_bytes)
+                    .split(';')
+                    .filter_map(|kv| kv.split_once('='))
+                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .collect())
+            }
+        }
+
+        let router =
+            Router::new(MockResponseHandler).route("my_service", "execute", HeadersResponseHandler);
+        let headers = Headers::from([("tenant".to_owned(), "foo".to_owned())]);
+
+        let mut h = router.clone();
+        assert_eq!(
+            h.make_request_bytes_with_headers(b"my_service", b"execute", b"req", &headers)?,
+            Some(b"tenant=foo;req".to_vec())
+        );
+        assert_eq!(
+            h.try_make_static_response_bytes(b"my_service", b"execute", b"tenant=foo;req")?,
+            None
+        );
+        assert_eq!(h.parse_response_headers(b"load=1;res")?["load"], "1");
+
+        let mut h = router.clone();
+        assert_eq!(
+            h.make_request_bytes_with_headers(b"my_service", b"other", b"req", &headers)?,
+            None
+        );
+        assert_eq!(
+            h.try_make_static_response_bytes(b"my_service", b"other", b"req")?,
+            None
+        );
+        assert!(h.parse_response_headers(b"load=1;res")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_new_response_bytes() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Debug, Clone, Default)]
+        struct CursorResponseHandler(usize);
+
+        impl ResponseHandler for CursorResponseHandler {
+            fn try_make_static_response_bytes(
+                &mut self,
+                _service_name: &'static [u8],
+                _fn_name: &'static [u8],
+                _request_bytes: &[u8],
+            ) -> Result<Option<Vec<u8>>, IoError> {
+                Ok(None)
+            }
+
+            fn parse_response_bytes(
+                &mut self,
+                response_bytes: &[u8],
+            ) -> Result<Option<usize>, IoError> {
+                Ok((response_bytes.len() >= 6).then_some(6))
+            }
+
+            fn reset(&mut self) {
+                self.0 = 0;
+            }
+
+            fn parse_new_response_bytes(
+                &mut self,
+                response_bytes: &[u8],
+                new_bytes_from: usize,
+            ) -> Result<Option<usize>, IoError> {
+                assert_eq!(new_bytes_from, self.0);
+                self.0 = response_bytes.len();
+                Ok((response_bytes.len() >= 6).then_some(6))
+            }
+        }
+
+        let mut h = Router::new(CursorResponseHandler::default());
+        h.reset();
+        assert_eq!(h.parse_new_response_bytes(b"foo", 0)?, None);
+        assert_eq!(h.parse_new_response_bytes(b"foobar", 3)?, Some(6));
+        h.reset();
+        assert_eq!(h.parse_new_response_bytes(b"foo", 0)?, None);
+
+        let mut h = CursorResponseHandler::default()
+            .or_else(MockResponseHandler)
+            .size_limit(6);
+        assert_eq!(h.parse_new_response_bytes(b"foo", 0)?, None);
+        assert_eq!(h.parse_new_response_bytes(b"foobar", 3)?, Some(6));
+        h.reset();
+        assert_eq!(h.parse_new_response_bytes(b"foo", 0)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_or_else() -> Result<(), Box<dyn std::error::Error>> {
+        let mut h = FixedResponseHandler(2).or_else(StaticResponseHandler(b"ok"));
+        assert_eq!(
+            h.try_make_static_response_bytes(b"my_service", b"my_fn", b"")?,
+            Some(b"ok".to_vec())
+        );
+        assert_eq!(h.parse_response_bytes(b"foo")?, Some(2));
+
+        let mut h = StaticResponseHandler(b"first").or_else(StaticResponseHandler(b"second"));
+        assert_eq!(
+            h.try_make_static_response_bytes(b"my_service", b"my_fn", b"")?,
+            Some(b"first".to_vec())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_limit() -> Result<(), Box<dyn std::error::Error>> {
+        let mut h = MockResponseHandler.size_limit(3);
+        assert_eq!(h.get_max_size(), 3);
+        assert_eq!(h.parse_response_bytes(b"foo")?, Some(3));
+        assert_eq!(
+            h.parse_response_bytes(b"foobar").unwrap_err().kind(),
+            IoErrorKind::InvalidData
+        );
+
+        let mut h = FixedResponseHandler(10).size_limit(3);
+        assert_eq!(h.parse_response_bytes(b"fo")?, None);
+        assert!(h.parse_response_bytes(b"foo").is_err());
+
+        let mut h = StaticResponseHandler(b"foobar").size_limit(3);
+        assert!(h
+            .try_make_static_response_bytes(b"my_service", b"my_fn", b"")
+            .is_err());
+
+        Ok(())
+    }
+}
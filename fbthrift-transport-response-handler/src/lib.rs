@@ -1,4 +1,10 @@
-use std::io::Error as IoError;
+use std::{collections::BTreeMap, io::Error as IoError};
+
+pub mod combinators;
+pub use combinators::{OrElse, ResponseHandlerExt, Router, SizeLimit};
+
+pub mod v2;
+pub use v2::{CallContext, ResponseHandlerV2, ResponseParseOutcome};
 
 //
 pub trait ResponseHandler: Clone {
@@ -14,6 +20,54 @@ pub trait ResponseHandler: Clone {
     ) -> Result<Option<Vec<u8>>, IoError>;
 
     fn parse_response_bytes(&mut self, response_bytes: &[u8]) -> Result<Option<usize>, IoError>;
+
+    // Called when a call starts, handlers keeping parse state between parses reset it here.
+    fn reset(&mut self) {}
+
+    // Called when a stream or sink element starts, within the same call.
+    fn reset_element(&mut self) {
+        self.reset()
+    }
+
+    // `response_bytes[new_bytes_from..]` is what was received since the previous parse of the
+    // same call, handlers keeping a cursor can scan only that instead of the whole buffer.
+    fn parse_new_response_bytes(
+        &mut self,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Result<Option<usize>, IoError> {
+        debug_assert!(new_bytes_from <= response_bytes.len());
+        self.parse_response_bytes(response_bytes)
+    }
+
+    // The request carrying `headers`, encoded by the handler, e.g. as THeader info headers, as the
+    // transport does not. None sends it unchanged, without them.
+    fn make_request_bytes_with_headers(
+        &mut self,
+        _service_name: &'static [u8],
+        _fn_name: &'static [u8],
+        _request_bytes: &[u8],
+        _headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        Ok(None)
+    }
+
+    // Called with the whole initial response, before any payload rewrite.
+    fn parse_response_headers(&mut self, _response_bytes: &[u8]) -> Result<Headers, IoError> {
+        Ok(Headers::new())
+    }
+}
+
+//
+pub type Headers = BTreeMap<String, String>;
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamElementKind {
+    Reply,
+    ApplicationEx,
+    // The end-of-stream marker, not yielded.
+    End,
 }
 
 //
@@ -37,6 +91,14 @@ impl ResponseHandler for MockResponseHandler {
     fn parse_response_bytes(&mut self, response_bytes: &[u8]) -> Result<Option<usize>, IoError> {
         Ok(Some(response_bytes.len()))
     }
+
+    fn parse_new_response_bytes(
+        &mut self,
+        response_bytes: &[u8],
+        _new_bytes_from: usize,
+    ) -> Result<Option<usize>, IoError> {
+        Ok(Some(response_bytes.len()))
+    }
 }
 
 #[cfg(test)]
@@ -53,6 +115,7 @@ mod tests {
         );
 
         assert_eq!(h.parse_response_bytes(&b"foo"[..])?, Some(3));
+        assert_eq!(h.parse_new_response_bytes(&b"foo"[..], 1)?, Some(3));
 
         Ok(())
     }
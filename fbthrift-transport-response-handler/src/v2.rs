@@ -0,0 +1,230 @@
+use core::task::{Context, Poll};
+use std::io::Error as IoError;
+
+use crate::{Headers, ResponseHandler, StreamElementKind};
+
+//
+#[derive(Debug, Clone, Copy)]
+pub struct CallContext<'a> {
+    pub service_name: &'static [u8],
+    pub fn_name: &'static [u8],
+    pub request_bytes: &'a [u8],
+    // Sequence number of the call on its transport, starts at 1, 0 when unassigned.
+    pub seq_id: u32,
+    // Starts at 1, 0 outside of interactions.
+    pub interaction_id: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseParseOutcome {
+    // The response is the first N bytes.
+    Complete(usize),
+    // The response takes the first `len` bytes, `payload` is returned instead of them.
+    Rewrite { len: usize, payload: Vec<u8> },
+    // Parsing again is pointless until at least N more bytes are received.
+    NeedMore(usize),
+}
+
+//
+pub trait ResponseHandlerV2: Clone {
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    fn poll_static_response_bytes(
+        &mut self,
+        cx: &mut Context<'_>,
+        ctx: &CallContext<'_>,
+    ) -> Poll<Result<Option<Vec<u8>>, IoError>>;
+
+    // See `ResponseHandler` for the reset hooks.
+    fn reset(&mut self) {}
+
+    fn reset_element(&mut self) {
+        self.reset()
+    }
+
+    // See `ResponseHandler::parse_new_response_bytes` for `new_bytes_from`.
+    fn poll_parse_response_bytes(
+        &mut self,
+        cx: &mut Context<'_>,
+        ctx: &CallContext<'_>,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Poll<Result<ResponseParseOutcome, IoError>>;
+
+    // Streaming calls only, for each parsed element after the initial response.
+    fn stream_element_kind(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        _element_bytes: &[u8],
+    ) -> Result<StreamElementKind, IoError> {
+        Ok(StreamElementKind::Reply)
+    }
+
+    // Streaming calls only, the frame granting the server `credits` more elements.
+    fn make_stream_credits_bytes(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        _credits: u32,
+    ) -> Option<Vec<u8>> {
+        None
+    }
+
+    // Streaming calls only, the frame asking the server to stop the stream.
+    fn make_stream_cancel_bytes(&mut self, _ctx: &CallContext<'_>) -> Option<Vec<u8>> {
+        None
+    }
+
+    // Sink calls only, the frame telling the server that no more items follow.
+    fn make_sink_complete_bytes(&mut self, _ctx: &CallContext<'_>) -> Option<Vec<u8>> {
+        None
+    }
+
+    // The frame terminating the interaction, written before the next call on its connection.
+    fn make_interaction_terminate_bytes(&mut self, _interaction_id: i64) -> Option<Vec<u8>> {
+        None
+    }
+
+    // See `ResponseHandler` for the headers hooks.
+    fn make_request_bytes_with_headers(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        _headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        Ok(None)
+    }
+
+    fn parse_response_headers(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        _response_bytes: &[u8],
+    ) -> Result<Headers, IoError> {
+        Ok(Headers::new())
+    }
+}
+
+impl<H> ResponseHandlerV2 for H
+where
+    H: ResponseHandler,
+{
+    fn name(&self) -> Option<&str> {
+        ResponseHandler::name(self)
+    }
+
+    fn poll_static_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        ctx: &CallContext<'_>,
+    ) -> Poll<Result<Option<Vec<u8>>, IoError>> {
+        Poll::Ready(self.try_make_static_response_bytes(
+            ctx.service_name,
+            ctx.fn_name,
+            ctx.request_bytes,
+        ))
+    }
+
+    fn reset(&mut self) {
+        ResponseHandler::reset(self)
+    }
+
+    fn reset_element(&mut self) {
+        ResponseHandler::reset_element(self)
+    }
+
+    fn poll_parse_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+        response_bytes: &[u8],
+        new_bytes_from: usize,
+    ) -> Poll<Result<ResponseParseOutcome, IoError>> {
+        let ret = self.parse_new_response_bytes(response_bytes, new_bytes_from);
+        Poll::Ready(ret.map(|n| match n {
+            Some(n) => ResponseParseOutcome::Complete(n),
+            None => ResponseParseOutcome::NeedMore(1),
+        }))
+    }
+
+    fn make_request_bytes_with_headers(
+        &mut self,
+        ctx: &CallContext<'_>,
+        headers: &Headers,
+    ) -> Result<Option<Vec<u8>>, IoError> {
+        ResponseHandler::make_request_bytes_with_headers(
+            self,
+            ctx.service_name,
+            ctx.fn_name,
+            ctx.request_bytes,
+            headers,
+        )
+    }
+
+    fn parse_response_headers(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        response_bytes: &[u8],
+    ) -> Result<Headers, IoError> {
+        ResponseHandler::parse_response_headers(self, response_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::task::Waker;
+
+    use crate::MockResponseHandler;
+
+    #[test]
+    fn test_blanket_impl() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Clone)]
+        struct FooResponseHandler;
+
+        impl ResponseHandler for FooResponseHandler {
+            fn try_make_static_response_bytes(
+                &mut self,
+                _service_name: &'static [u8],
+                fn_name: &'static [u8],
+                _request_bytes: &[u8],
+            ) -> Result<Option<Vec<u8>>, IoError> {
+                Ok((fn_name == b"ping").then(|| b"pong".to_vec()))
+            }
+
+            fn parse_response_bytes(
+                &mut self,
+                response_bytes: &[u8],
+            ) -> Result<Option<usize>, IoError> {
+                Ok((response_bytes.len() >= 3).then_some(3))
+            }
+        }
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let ctx = CallContext {
+            service_name: b"my_service",
+            fn_name: b"ping",
+            request_bytes: b"",
+            seq_id: 1,
+            interaction_id: 0,
+        };
+
+        let mut h = FooResponseHandler;
+        assert!(matches!(
+            h.poll_static_response_bytes(&mut cx, &ctx),
+            Poll::Ready(Ok(Some(bytes))) if bytes == b"pong"
+        ));
+        assert!(matches!(
+            h.poll_parse_response_bytes(&mut cx, &ctx, b"fo", 0),
+            Poll::Ready(Ok(ResponseParseOutcome::NeedMore(1)))
+        ));
+        assert!(matches!(
+            h.poll_parse_response_bytes(&mut cx, &ctx, b"foobar", 2),
+            Poll::Ready(Ok(ResponseParseOutcome::Complete(3)))
+        ));
+
+        assert_eq!(ResponseHandlerV2::name(&MockResponseHandler), Some("Mock"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,489 @@
+use core::{ffi::CStr, time::Duration};
+use std::{
+    io::{Cursor, Error as IoError, ErrorKind as IoErrorKind},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use async_lock::Mutex as AsyncMutex;
+use async_sleep::Sleepble;
+use bytes::{Bytes, BytesMut};
+use fbthrift::{Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use fbthrift_transport_response_handler::ResponseHandlerV2;
+use futures_util::{
+    future::BoxFuture,
+    io::{AsyncRead, AsyncWrite},
+};
+
+use crate::{
+    configuration::AsyncTransportConfiguration,
+    connector::Connector,
+    retry::is_retryable_error,
+    stats::TransportStats,
+    transport::{AsyncTransport, AsyncTransportRpcOptions},
+};
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BalanceStrategy {
+    #[default]
+    RoundRobin,
+    LeastOutstanding,
+    PowerOfTwoChoices,
+}
+
+//
+struct Endpoint<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    connector: Arc<dyn Connector<S>>,
+    transport: Mutex<Option<Arc<AsyncTransport<S, SLEEP, H>>>>,
+    // One call at a time on the connection, held from connecting to the response.
+    slot: AsyncMutex<()>,
+    outstanding: AtomicUsize,
+    ejected_until: Mutex<Option<Instant>>,
+    probing: AtomicBool,
+}
+
+impl<S, SLEEP, H> Endpoint<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn is_healthy(&self) -> bool {
+        self.ejected_until
+            .lock()
+            .map(|ejected_until| ejected_until.is_none())
+            .unwrap_or(false)
+    }
+
+    fn try_start_probe(&self) -> bool {
+        let probe_due = match self.ejected_until.lock() {
+            Ok(ejected_until) => matches!(*ejected_until, Some(until) if until <= Instant::now()),
+            Err(_) => false,
+        };
+
+        probe_due
+            && self
+                .probing
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+    }
+
+    fn mark_healthy(&self) {
+        if let Ok(mut ejected_until) = self.ejected_until.lock() {
+            *ejected_until = None;
+        }
+        self.probing.store(false, Ordering::Release);
+    }
+
+    fn on_call_complete<T>(&self, ret: &anyhow::Result<T>, eject_duration: Duration) {
+        match ret {
+            Err(err) if is_retryable_error(err) => self.eject(eject_duration),
+            _ => self.mark_healthy(),
+        }
+    }
+
+    fn eject(&self, dur: Duration) {
+        if let Ok(mut transport) = self.transport.lock() {
+            *transport = None;
+        }
+        if let Ok(mut ejected_until) = self.ejected_until.lock() {
+            *ejected_until = Some(Instant::now() + dur);
+        }
+        self.probing.store(false, Ordering::Release);
+    }
+}
+
+struct OutstandingGuard<'a>(&'a AtomicUsize);
+
+impl<'a> OutstandingGuard<'a> {
+    fn new(outstanding: &'a AtomicUsize) -> Self {
+        outstanding.fetch_add(1, Ordering::AcqRel);
+        Self(outstanding)
+    }
+}
+
+impl Drop for OutstandingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// Held by the call probing an ejected endpoint, so that dropping it, e.g. on a timeout, lets the
+// next call probe again.
+struct ProbeGuard<'a>(Option<&'a AtomicBool>);
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(probing) = self.0 {
+            probing.store(false, Ordering::Release);
+        }
+    }
+}
+
+// A call dropped before its end leaves its response on the connection, the next call reconnects.
+struct DesyncGuard<'a, S, SLEEP, H>(Option<&'a AsyncTransport<S, SLEEP, H>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin;
+
+impl<S, SLEEP, H> Drop for DesyncGuard<'_, S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn drop(&mut self) {
+        if let Some(transport) = self.0 {
+            transport.desync();
+        }
+    }
+}
+
+// The endpoint index and the interaction created on its connection.
+type Bound<S, SLEEP, H> = (usize, Arc<AsyncTransport<S, SLEEP, H>>);
+
+// An interaction, bound to an endpoint on its first call, as connecting is async.
+struct Pinned<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    method_name: &'static CStr,
+    bound: AsyncMutex<Option<Bound<S, SLEEP, H>>>,
+}
+
+//
+struct Inner<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    endpoints: Vec<Endpoint<S, SLEEP, H>>,
+    configuration: AsyncTransportConfiguration<H>,
+    strategy: BalanceStrategy,
+    eject_duration: Duration,
+    next: AtomicUsize,
+}
+
+impl<S, SLEEP, H> Inner<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn pick(&self) -> Option<(usize, ProbeGuard<'_>)> {
+        if let Some(i) = self.endpoints.iter().position(|ep| ep.try_start_probe()) {
+            return Some((i, ProbeGuard(Some(&self.endpoints[i].probing))));
+        }
+
+        self.pick_healthy().map(|i| (i, ProbeGuard(None)))
+    }
+
+    fn pick_healthy(&self) -> Option<usize> {
+        let healthy = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, ep)| ep.is_healthy())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if healthy.is_empty() {
+            return None;
+        }
+
+        let outstanding = |i: &usize| self.endpoints[*i].outstanding.load(Ordering::Acquire);
+
+        match self.strategy {
+            BalanceStrategy::RoundRobin => {
+                let n = self.next.fetch_add(1, Ordering::Relaxed);
+                Some(healthy[n % healthy.len()])
+            }
+            BalanceStrategy::LeastOutstanding => healthy.iter().copied().min_by_key(outstanding),
+            BalanceStrategy::PowerOfTwoChoices => {
+                if healthy.len() == 1 {
+                    return Some(healthy[0]);
+                }
+                let a = fastrand::usize(..healthy.len());
+                let mut b = fastrand::usize(..healthy.len() - 1);
+                if b >= a {
+                    b += 1;
+                }
+                let (a, b) = (healthy[a], healthy[b]);
+                Some(if outstanding(&b) < outstanding(&a) {
+                    b
+                } else {
+                    a
+                })
+            }
+        }
+    }
+
+    // Called with the endpoint's slot held, so only one caller connects.
+    async fn get_or_connect(&self, i: usize) -> Result<Arc<AsyncTransport<S, SLEEP, H>>, IoError> {
+        let ep = &self.endpoints[i];
+
+        if let Some(transport) = ep
+            .transport
+            .lock()
+            .map_err(|err| IoError::other(err.to_string()))?
+            .as_ref()
+        {
+            return Ok(transport.clone());
+        }
+
+        let transport = Arc::new(
+            AsyncTransport::with_connector(ep.connector.clone(), self.configuration.clone())
+                .await?,
+        );
+
+        *ep.transport
+            .lock()
+            .map_err(|err| IoError::other(err.to_string()))? = Some(transport.clone());
+
+        Ok(transport)
+    }
+}
+
+impl<S, SLEEP, H> Inner<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    SLEEP: Sleepble + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
+{
+    // Holds the endpoint's slot as ordinary calls do, as the interaction shares its connection.
+    async fn call_pinned(
+        &self,
+        pinned: &Pinned<S, SLEEP, H>,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<AsyncTransport<S, SLEEP, H>>,
+        rpc_options: AsyncTransportRpcOptions,
+    ) -> anyhow::Result<FramingDecoded<AsyncTransport<S, SLEEP, H>>> {
+        let mut bound = pinned.bound.lock().await;
+
+        let (i, _probe) = match &*bound {
+            Some((i, _)) => (*i, ProbeGuard(None)),
+            None => self
+                .pick()
+                .ok_or_else(|| IoError::new(IoErrorKind::NotConnected, "No available endpoint"))?,
+        };
+        let ep = &self.endpoints[i];
+        let _guard = OutstandingGuard::new(&ep.outstanding);
+        let _slot = ep.slot.lock().await;
+
+        let interaction = match &*bound {
+            Some((_, interaction)) => interaction.clone(),
+            None => {
+                let transport = match self.get_or_connect(i).await {
+                    Ok(transport) => transport,
+                    Err(err) => {
+                        ep.eject(self.eject_duration);
+                        return Err(err.into());
+                    }
+                };
+                let interaction = Arc::new(transport.create_interaction(pinned.method_name)?);
+                *bound = Some((i, interaction.clone()));
+                interaction
+            }
+        };
+
+        let mut desync_guard = DesyncGuard(Some(&interaction));
+        let ret = interaction
+            .call(service_name, fn_name, req, rpc_options)
+            .await;
+        desync_guard.0 = None;
+        ep.on_call_complete(&ret, self.eject_duration);
+
+        ret
+    }
+}
+
+//
+// Each endpoint has one connection running one call at a time, the other calls on the endpoint
+// wait for it and count as outstanding.
+pub struct BalancedTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    inner: Arc<Inner<S, SLEEP, H>>,
+    // Set on interactions, every call goes to that connection.
+    pinned: Option<Arc<Pinned<S, SLEEP, H>>>,
+}
+
+impl<S, SLEEP, H> BalancedTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    pub fn new(
+        connectors: Vec<Arc<dyn Connector<S>>>,
+        configuration: AsyncTransportConfiguration<H>,
+        strategy: BalanceStrategy,
+    ) -> Self {
+        Self::with_eject_duration(connectors, configuration, strategy, Duration::from_secs(10))
+    }
+
+    pub fn with_eject_duration(
+        connectors: Vec<Arc<dyn Connector<S>>>,
+        configuration: AsyncTransportConfiguration<H>,
+        strategy: BalanceStrategy,
+        eject_duration: Duration,
+    ) -> Self {
+        debug_assert!(!connectors.is_empty());
+
+        let endpoints = connectors
+            .into_iter()
+            .map(|connector| Endpoint {
+                connector,
+                transport: Mutex::new(None),
+                slot: AsyncMutex::new(()),
+                outstanding: AtomicUsize::new(0),
+                ejected_until: Mutex::new(None),
+                probing: AtomicBool::new(false),
+            })
+            .collect();
+
+        Self {
+            inner: Arc::new(Inner {
+                endpoints,
+                configuration,
+                strategy,
+                eject_duration,
+                next: AtomicUsize::new(0),
+            }),
+            pinned: None,
+        }
+    }
+
+    pub fn get_strategy(&self) -> BalanceStrategy {
+        self.inner.strategy
+    }
+
+    pub fn get_eject_duration(&self) -> Duration {
+        self.inner.eject_duration
+    }
+
+    pub fn endpoints_len(&self) -> usize {
+        self.inner.endpoints.len()
+    }
+
+    pub fn healthy_endpoints_len(&self) -> usize {
+        self.inner
+            .endpoints
+            .iter()
+            .filter(|ep| ep.is_healthy())
+            .count()
+    }
+
+    // In endpoints order, None while not connected. Ejecting an endpoint drops its connection,
+    // so its counters start over once it reconnects.
+    pub fn endpoints_stats(&self) -> Vec<Option<TransportStats>> {
+        self.inner
+            .endpoints
+            .iter()
+            .map(|ep| {
+                ep.transport
+                    .lock()
+                    .ok()
+                    .and_then(|transport| transport.as_ref().map(|transport| transport.stats()))
+            })
+            .collect()
+    }
+}
+
+impl<S, SLEEP, H> Framing for BalancedTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        Self::EncBuf::with_capacity(cap)
+    }
+}
+
+impl<S, SLEEP, H> Transport for BalancedTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    SLEEP: Sleepble + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
+{
+    type RpcOptions = AsyncTransportRpcOptions;
+
+    fn call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let inner = self.inner.clone();
+
+        if let Some(pinned) = self.pinned.clone() {
+            return Box::pin(async move {
+                inner
+                    .call_pinned(&pinned, service_name, fn_name, req, rpc_options)
+                    .await
+            });
+        }
+
+        Box::pin(async move {
+            let (i, _probe) = inner
+                .pick()
+                .ok_or_else(|| IoError::new(IoErrorKind::NotConnected, "No available endpoint"))?;
+            let ep = &inner.endpoints[i];
+            let _guard = OutstandingGuard::new(&ep.outstanding);
+            let _slot = ep.slot.lock().await;
+
+            let transport = match inner.get_or_connect(i).await {
+                Ok(transport) => transport,
+                Err(err) => {
+                    ep.eject(inner.eject_duration);
+                    return Err(err.into());
+                }
+            };
+
+            let mut desync_guard = DesyncGuard(Some(&transport));
+            let ret = transport
+                .call(service_name, fn_name, req, rpc_options)
+                .await;
+            desync_guard.0 = None;
+            ep.on_call_complete(&ret, inner.eject_duration);
+
+            ret
+        })
+    }
+
+    // Bound to an endpoint picked on its first call, later calls go to that connection.
+    fn create_interaction(&self, method_name: &'static CStr) -> Result<Self, anyhow::Error> {
+        if self.pinned.is_some() {
+            anyhow::bail!("Nested interactions are not supported");
+        }
+
+        Ok(Self {
+            inner: self.inner.clone(),
+            pinned: Some(Arc::new(Pinned {
+                method_name,
+                bound: AsyncMutex::new(None),
+            })),
+        })
+    }
+}
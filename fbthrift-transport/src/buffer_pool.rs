@@ -0,0 +1,173 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use bytes::BytesMut;
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BufferPoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub recycled: u64,
+    pub discarded: u64,
+}
+
+//
+#[derive(Debug)]
+struct SizeClass {
+    size: usize,
+    free: Mutex<Vec<BytesMut>>,
+}
+
+#[derive(Debug)]
+pub struct BufferPool {
+    classes: Vec<SizeClass>,
+    max_buffers_per_class: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    recycled: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(vec![1024, 1024 * 4, 1024 * 16, 1024 * 64], 64)
+    }
+}
+
+impl BufferPool {
+    pub fn new(mut size_classes: Vec<usize>, max_buffers_per_class: usize) -> Self {
+        debug_assert!(!size_classes.is_empty());
+        debug_assert!(size_classes.iter().all(|size| *size > 0));
+
+        size_classes.sort_unstable();
+        size_classes.dedup();
+
+        Self {
+            classes: size_classes
+                .into_iter()
+                .map(|size| SizeClass {
+                    size,
+                    free: Mutex::new(vec![]),
+                })
+                .collect(),
+            max_buffers_per_class,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get_size_classes(&self) -> Vec<usize> {
+        self.classes.iter().map(|class| class.size).collect()
+    }
+
+    pub fn get_max_buffers_per_class(&self) -> usize {
+        self.max_buffers_per_class
+    }
+
+    // Returns an empty buffer with at least `capacity` bytes of capacity.
+    pub fn get(&self, capacity: usize) -> BytesMut {
+        let class = match self.classes.iter().find(|class| class.size >= capacity) {
+            Some(class) => class,
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return BytesMut::with_capacity(capacity);
+            }
+        };
+
+        if let Some(buf) = class.free.lock().ok().and_then(|mut free| free.pop()) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return buf;
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        BytesMut::with_capacity(class.size)
+    }
+
+    pub fn put(&self, mut buf: BytesMut) {
+        buf.clear();
+
+        let class = self
+            .classes
+            .iter()
+            .rev()
+            .find(|class| class.size <= buf.capacity());
+        let recycled = class
+            .and_then(|class| class.free.lock().ok())
+            .map(|mut free| {
+                if free.len() < self.max_buffers_per_class {
+                    free.push(buf);
+                    true
+                } else {
+                    false
+                }
+            })
+            .unwrap_or(false);
+
+        if recycled {
+            self.recycled.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            recycled: self.recycled.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_put() {
+        let pool = BufferPool::new(vec![64, 16], 1);
+        assert_eq!(pool.get_size_classes(), vec![16, 64]);
+
+        let buf = pool.get(10);
+        assert!(buf.capacity() >= 16);
+        pool.put(buf);
+        let buf = pool.get(16);
+        assert!(buf.capacity() >= 16);
+        assert_eq!(
+            pool.stats(),
+            BufferPoolStats {
+                hits: 1,
+                misses: 1,
+                recycled: 1,
+                discarded: 0
+            }
+        );
+
+        pool.put(BytesMut::with_capacity(16));
+        // Over the per class limit.
+        pool.put(buf);
+        // Smaller than every class.
+        pool.put(BytesMut::with_capacity(8));
+        // Larger than every class.
+        let buf = pool.get(128);
+        assert!(buf.capacity() >= 128);
+        pool.put(buf);
+        assert!(pool.get(64).capacity() >= 64);
+
+        assert_eq!(
+            pool.stats(),
+            BufferPoolStats {
+                hits: 2,
+                misses: 2,
+                recycled: 3,
+                discarded: 2
+            }
+        );
+    }
+}
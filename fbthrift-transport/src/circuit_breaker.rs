@@ -0,0 +1,437 @@
+use core::{ffi::CStr, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use fbthrift::{ClientStreamElement, Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use futures_util::{
+    future::{self, BoxFuture},
+    stream::BoxStream,
+};
+
+//
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfiguration {
+    failure_rate_threshold: f64,
+    minimum_calls: usize,
+    window_size: usize,
+    cool_down: Duration,
+    half_open_max_calls: usize,
+    per_function: bool,
+}
+
+impl Default for CircuitBreakerConfiguration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreakerConfiguration {
+    pub fn new() -> Self {
+        Self {
+            failure_rate_threshold: 0.5,
+            minimum_calls: 10,
+            window_size: 20,
+            cool_down: Duration::from_secs(10),
+            half_open_max_calls: 1,
+            per_function: false,
+        }
+    }
+
+    pub fn set_failure_rate_threshold(&mut self, rate: f64) {
+        debug_assert!(rate > 0.0 && rate <= 1.0);
+        self.failure_rate_threshold = rate;
+    }
+
+    pub fn get_failure_rate_threshold(&self) -> f64 {
+        self.failure_rate_threshold
+    }
+
+    pub fn set_minimum_calls(&mut self, n: usize) {
+        debug_assert!(n > 0 && n <= self.window_size);
+        self.minimum_calls = n;
+    }
+
+    pub fn get_minimum_calls(&self) -> usize {
+        self.minimum_calls
+    }
+
+    pub fn set_window_size(&mut self, n: usize) {
+        debug_assert!(n >= self.minimum_calls);
+        self.window_size = n;
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.window_size
+    }
+
+    pub fn set_cool_down(&mut self, dur: Duration) {
+        self.cool_down = dur;
+    }
+
+    pub fn get_cool_down(&self) -> Duration {
+        self.cool_down
+    }
+
+    pub fn set_half_open_max_calls(&mut self, n: usize) {
+        debug_assert!(n > 0);
+        self.half_open_max_calls = n;
+    }
+
+    pub fn get_half_open_max_calls(&self) -> usize {
+        self.half_open_max_calls
+    }
+
+    pub fn set_per_function(&mut self, per_function: bool) {
+        self.per_function = per_function;
+    }
+
+    pub fn get_per_function(&self) -> bool {
+        self.per_function
+    }
+}
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitOpenError {
+    pub service_name: &'static CStr,
+    pub fn_name: &'static CStr,
+}
+
+impl core::fmt::Display for CircuitOpenError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Circuit breaker is open for {}.{}",
+            self.service_name.to_string_lossy(),
+            self.fn_name.to_string_lossy()
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpenError {}
+
+//
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    // Bumped on every state change. Calls acquired before are not counted once they complete.
+    generation: u64,
+    window: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    half_open_in_flight: usize,
+    half_open_successes: usize,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            generation: 0,
+            window: VecDeque::new(),
+            opened_at: None,
+            half_open_in_flight: 0,
+            half_open_successes: 0,
+        }
+    }
+}
+
+impl Breaker {
+    fn open(&mut self) {
+        self.state = CircuitState::Open;
+        self.generation += 1;
+        self.opened_at = Some(Instant::now());
+        self.window.clear();
+        self.half_open_in_flight = 0;
+        self.half_open_successes = 0;
+    }
+
+    fn close(&mut self) {
+        *self = Self {
+            generation: self.generation + 1,
+            ..Self::default()
+        };
+    }
+
+    // Returns the generation the call is acquired in.
+    fn acquire(&mut self, c: &CircuitBreakerConfiguration) -> Option<u64> {
+        if self.state == CircuitState::Open {
+            match self.opened_at {
+                Some(opened_at) if opened_at.elapsed() >= c.cool_down => {
+                    self.state = CircuitState::HalfOpen;
+                    self.generation += 1;
+                }
+                _ => return None,
+            }
+        }
+
+        if self.state == CircuitState::HalfOpen {
+            if self.half_open_in_flight >= c.half_open_max_calls {
+                return None;
+            }
+            self.half_open_in_flight += 1;
+        }
+
+        Some(self.generation)
+    }
+
+    fn release(&mut self, generation: u64) {
+        if generation == self.generation && self.state == CircuitState::HalfOpen {
+            self.half_open_in_flight = self.half_open_in_flight.saturating_sub(1);
+        }
+    }
+
+    fn record(&mut self, c: &CircuitBreakerConfiguration, generation: u64, success: bool) {
+        if generation != self.generation {
+            return;
+        }
+
+        match self.state {
+            CircuitState::Closed => {
+                self.window.push_back(success);
+                while self.window.len() > c.window_size {
+                    self.window.pop_front();
+                }
+
+                if self.window.len() >= c.minimum_calls {
+                    let failures = self.window.iter().filter(|x| !**x).count();
+                    if failures as f64 / self.window.len() as f64 >= c.failure_rate_threshold {
+                        self.open();
+                    }
+                }
+            }
+            CircuitState::HalfOpen => {
+                self.half_open_in_flight = self.half_open_in_flight.saturating_sub(1);
+                if success {
+                    self.half_open_successes += 1;
+                    if self.half_open_successes >= c.half_open_max_calls {
+                        self.close();
+                    }
+                } else {
+                    self.open();
+                }
+            }
+            CircuitState::Open => {}
+        }
+    }
+}
+
+type BreakerKey = Option<(&'static CStr, &'static CStr)>;
+
+struct Inner {
+    configuration: CircuitBreakerConfiguration,
+    breakers: Mutex<HashMap<BreakerKey, Breaker>>,
+}
+
+impl Inner {
+    fn key(&self, service_name: &'static CStr, fn_name: &'static CStr) -> BreakerKey {
+        self.configuration
+            .per_function
+            .then_some((service_name, fn_name))
+    }
+
+    fn with_breaker<R>(&self, key: BreakerKey, f: impl FnOnce(&mut Breaker) -> R) -> Option<R> {
+        let mut breakers = self.breakers.lock().ok()?;
+        Some(f(breakers.entry(key).or_default()))
+    }
+}
+
+struct Permit {
+    inner: Arc<Inner>,
+    key: BreakerKey,
+    generation: u64,
+    done: bool,
+}
+
+impl Permit {
+    fn record(mut self, success: bool) {
+        self.done = true;
+        let c = &self.inner.configuration;
+        let generation = self.generation;
+        self.inner
+            .with_breaker(self.key, |breaker| breaker.record(c, generation, success));
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if !self.done {
+            let generation = self.generation;
+            self.inner
+                .with_breaker(self.key, |breaker| breaker.release(generation));
+        }
+    }
+}
+
+//
+pub struct CircuitBreakerTransport<T> {
+    transport: T,
+    inner: Arc<Inner>,
+}
+
+impl<T> CircuitBreakerTransport<T> {
+    pub fn new(transport: T, configuration: CircuitBreakerConfiguration) -> Self {
+        Self {
+            transport,
+            inner: Arc::new(Inner {
+                configuration,
+                breakers: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_configuration(&self) -> &CircuitBreakerConfiguration {
+        &self.inner.configuration
+    }
+
+    pub fn state(&self, service_name: &'static CStr, fn_name: &'static CStr) -> CircuitState {
+        let key = self.inner.key(service_name, fn_name);
+        self.inner
+            .with_breaker(key, |breaker| breaker.state)
+            .unwrap_or(CircuitState::Open)
+    }
+
+    fn acquire(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+    ) -> Result<Permit, CircuitOpenError> {
+        let key = self.inner.key(service_name, fn_name);
+        let c = &self.inner.configuration;
+        match self.inner.with_breaker(key, |breaker| breaker.acquire(c)) {
+            Some(Some(generation)) => Ok(Permit {
+                inner: self.inner.clone(),
+                key,
+                generation,
+                done: false,
+            }),
+            _ => Err(CircuitOpenError {
+                service_name,
+                fn_name,
+            }),
+        }
+    }
+}
+
+impl<T> Framing for CircuitBreakerTransport<T>
+where
+    T: Framing,
+{
+    type EncBuf = T::EncBuf;
+    type DecBuf = T::DecBuf;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        T::enc_with_capacity(cap)
+    }
+}
+
+impl<T> Transport for CircuitBreakerTransport<T>
+where
+    T: Transport,
+{
+    type RpcOptions = T::RpcOptions;
+
+    fn call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let permit = match self.acquire(service_name, fn_name) {
+            Ok(permit) => permit,
+            Err(err) => return Box::pin(future::ready(Err(err.into()))),
+        };
+        let call = self.transport.call(service_name, fn_name, req, rpc_options);
+
+        Box::pin(async move {
+            let ret = call.await;
+            permit.record(ret.is_ok());
+            ret
+        })
+    }
+
+    // Counts as one call, by the outcome of the initial response.
+    fn call_stream(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<
+        'static,
+        anyhow::Result<(
+            FramingDecoded<Self>,
+            BoxStream<'static, anyhow::Result<ClientStreamElement<FramingDecoded<Self>>>>,
+        )>,
+    > {
+        let permit = match self.acquire(service_name, fn_name) {
+            Ok(permit) => permit,
+            Err(err) => return Box::pin(future::ready(Err(err.into()))),
+        };
+        let open = self
+            .transport
+            .call_stream(service_name, fn_name, req, rpc_options);
+
+        Box::pin(async move {
+            let ret = open.await;
+            permit.record(ret.is_ok());
+            ret
+        })
+    }
+
+    // The interaction shares the breakers.
+    fn create_interaction(&self, method_name: &'static CStr) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            transport: self.transport.create_interaction(method_name)?,
+            inner: self.inner.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_ignores_stale_permits() {
+        let mut c = CircuitBreakerConfiguration::new();
+        c.set_minimum_calls(1);
+        c.set_cool_down(Duration::ZERO);
+
+        let mut b = Breaker::default();
+        let slow = b.acquire(&c).expect("");
+        let failed = b.acquire(&c).expect("");
+        let dropped = b.acquire(&c).expect("");
+        b.record(&c, failed, false);
+        assert_eq!(b.state, CircuitState::Open);
+
+        let probe = b.acquire(&c).expect("");
+        assert_eq!(b.state, CircuitState::HalfOpen);
+        assert_eq!(b.acquire(&c), None);
+
+        // Acquired while closed, neither a probe result nor a released probe.
+        b.record(&c, slow, true);
+        b.release(dropped);
+        assert_eq!(b.state, CircuitState::HalfOpen);
+        assert_eq!(b.half_open_in_flight, 1);
+
+        b.record(&c, probe, true);
+        assert_eq!(b.state, CircuitState::Closed);
+        b.record(&c, probe, false);
+        assert_eq!(b.state, CircuitState::Closed);
+    }
+}
@@ -1,52 +1,76 @@
 use core::time::Duration;
+use std::sync::Arc;
 
-use fbthrift_transport_response_handler::ResponseHandler;
+use fbthrift_transport_response_handler::ResponseHandlerV2;
+
+use crate::{buffer_pool::BufferPool, observer::CallObserver, wire_dump::WireDump};
 
 //
 #[derive(Clone)]
 pub struct AsyncTransportConfiguration<H>
 where
-    H: ResponseHandler,
+    H: ResponseHandlerV2,
 {
     buf_size: usize,
     max_buf_size: usize,
     read_timeout: Duration,
+    write_timeout: Duration,
     max_parse_response_bytes_count: u8,
+    stream_credits: u32,
     pub(crate) response_handler: H,
+    call_observer: Option<Arc<dyn CallObserver>>,
+    wire_dump: Option<WireDump>,
+    buffer_pool: Option<Arc<BufferPool>>,
 }
 
 impl<H> core::fmt::Debug for AsyncTransportConfiguration<H>
 where
-    H: ResponseHandler,
+    H: ResponseHandlerV2,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AsyncTransportConfiguration")
             .field("buf_size", &self.buf_size)
             .field("max_buf_size", &self.max_buf_size)
             .field("read_timeout", &self.read_timeout)
+            .field("write_timeout", &self.write_timeout)
             .field(
                 "max_parse_response_bytes_count",
                 &self.max_parse_response_bytes_count,
             )
+            .field("stream_credits", &self.stream_credits)
             .field(
                 "response_handler",
                 &self.response_handler.name().unwrap_or_default(),
             )
+            .field("call_observer", &self.call_observer.is_some())
+            .field("wire_dump", &self.wire_dump)
+            .field("buffer_pool", &self.buffer_pool.is_some())
             .finish()
     }
 }
 
 impl<H> AsyncTransportConfiguration<H>
 where
-    H: ResponseHandler,
+    H: ResponseHandlerV2,
 {
+    pub fn builder(response_handler: H) -> AsyncTransportConfigurationBuilder<H> {
+        AsyncTransportConfigurationBuilder {
+            inner: Self::new(response_handler),
+        }
+    }
+
     pub fn new(response_handler: H) -> Self {
         Self {
             buf_size: 1024,
             max_buf_size: 1024 * 4,
             read_timeout: Duration::from_secs(5),
+            write_timeout: Duration::from_secs(5),
             max_parse_response_bytes_count: 3,
+            stream_credits: 0,
             response_handler,
+            call_observer: None,
+            wire_dump: None,
+            buffer_pool: None,
         }
     }
 
@@ -68,15 +92,27 @@ where
         self.max_buf_size
     }
 
-    pub fn set_read_timeout(&mut self, timeout_ms: u32) {
-        debug_assert!(timeout_ms > 0);
-        self.read_timeout = Duration::from_millis(timeout_ms as u64);
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        debug_assert!(!timeout.is_zero());
+        self.read_timeout = timeout;
     }
 
     pub fn get_read_timeout(&self) -> Duration {
         self.read_timeout
     }
 
+    // Applies to every write and flush on the connection: the request, the flush of oneway calls,
+    // the frames of streaming and sink calls, and the queued frames written before a request or on
+    // close. It restarts after each write that makes progress.
+    pub fn set_write_timeout(&mut self, timeout: Duration) {
+        debug_assert!(!timeout.is_zero());
+        self.write_timeout = timeout;
+    }
+
+    pub fn get_write_timeout(&self) -> Duration {
+        self.write_timeout
+    }
+
     pub fn set_max_parse_response_bytes_count(&mut self, size: u8) {
         debug_assert!(size > 0);
         self.max_parse_response_bytes_count = size;
@@ -85,6 +121,207 @@ where
     pub fn get_max_parse_response_bytes_count(&self) -> u8 {
         self.max_parse_response_bytes_count
     }
+
+    // Elements granted to the server per batch on streaming calls, 0 disables flow control.
+    pub fn set_stream_credits(&mut self, credits: u32) {
+        self.stream_credits = credits;
+    }
+
+    pub fn get_stream_credits(&self) -> u32 {
+        self.stream_credits
+    }
+
+    pub fn set_call_observer(&mut self, observer: Arc<dyn CallObserver>) {
+        self.call_observer = Some(observer);
+    }
+
+    pub fn get_call_observer(&self) -> Option<&Arc<dyn CallObserver>> {
+        self.call_observer.as_ref()
+    }
+
+    pub fn set_wire_dump(&mut self, wire_dump: WireDump) {
+        self.wire_dump = Some(wire_dump);
+    }
+
+    pub fn get_wire_dump(&self) -> Option<&WireDump> {
+        self.wire_dump.as_ref()
+    }
+
+    pub fn set_buffer_pool(&mut self, pool: Arc<BufferPool>) {
+        self.buffer_pool = Some(pool);
+    }
+
+    pub fn get_buffer_pool(&self) -> Option<&Arc<BufferPool>> {
+        self.buffer_pool.as_ref()
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.buf_size == 0 {
+            return Err(ConfigError::ZeroBufSize);
+        }
+        if self.buf_size > self.max_buf_size {
+            return Err(ConfigError::BufSizeExceedsMaxBufSize {
+                buf_size: self.buf_size,
+                max_buf_size: self.max_buf_size,
+            });
+        }
+        if self.read_timeout.is_zero() {
+            return Err(ConfigError::ZeroReadTimeout);
+        }
+        if self.write_timeout.is_zero() {
+            return Err(ConfigError::ZeroWriteTimeout);
+        }
+        if self.max_parse_response_bytes_count == 0 {
+            return Err(ConfigError::ZeroMaxParseResponseBytesCount);
+        }
+        Ok(())
+    }
+}
+
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    ZeroBufSize,
+    BufSizeExceedsMaxBufSize {
+        buf_size: usize,
+        max_buf_size: usize,
+    },
+    ZeroReadTimeout,
+    ZeroWriteTimeout,
+    ZeroMaxParseResponseBytesCount,
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ZeroBufSize => write!(f, "buf_size must be greater than 0"),
+            Self::BufSizeExceedsMaxBufSize {
+                buf_size,
+                max_buf_size,
+            } => write!(
+                f,
+                "buf_size {buf_size} must not be greater than max_buf_size {max_buf_size}"
+            ),
+            Self::ZeroReadTimeout => write!(f, "read_timeout must be greater than 0"),
+            Self::ZeroWriteTimeout => write!(f, "write_timeout must be greater than 0"),
+            Self::ZeroMaxParseResponseBytesCount => {
+                write!(f, "max_parse_response_bytes_count must be greater than 0")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+//
+#[derive(Debug, Clone)]
+pub struct AsyncTransportConfigurationBuilder<H>
+where
+    H: ResponseHandlerV2,
+{
+    inner: AsyncTransportConfiguration<H>,
+}
+
+impl<H> AsyncTransportConfigurationBuilder<H>
+where
+    H: ResponseHandlerV2,
+{
+    pub fn buf_size(mut self, size: usize) -> Self {
+        self.inner.buf_size = size;
+        self
+    }
+
+    pub fn max_buf_size(mut self, size: usize) -> Self {
+        self.inner.max_buf_size = size;
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.inner.read_timeout = timeout;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.inner.write_timeout = timeout;
+        self
+    }
+
+    pub fn max_parse_response_bytes_count(mut self, count: u8) -> Self {
+        self.inner.max_parse_response_bytes_count = count;
+        self
+    }
+
+    pub fn stream_credits(mut self, credits: u32) -> Self {
+        self.inner.stream_credits = credits;
+        self
+    }
+
+    pub fn call_observer(mut self, observer: Arc<dyn CallObserver>) -> Self {
+        self.inner.call_observer = Some(observer);
+        self
+    }
+
+    pub fn wire_dump(mut self, wire_dump: WireDump) -> Self {
+        self.inner.wire_dump = Some(wire_dump);
+        self
+    }
+
+    pub fn buffer_pool(mut self, pool: Arc<BufferPool>) -> Self {
+        self.inner.buffer_pool = Some(pool);
+        self
+    }
+
+    pub fn build(self) -> Result<AsyncTransportConfiguration<H>, ConfigError> {
+        self.inner.validate()?;
+        Ok(self.inner)
+    }
+}
+
+//
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsyncTransportConfigurationData {
+    pub buf_size: Option<usize>,
+    pub max_buf_size: Option<usize>,
+    #[serde(with = "humantime_serde")]
+    pub read_timeout: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    pub write_timeout: Option<Duration>,
+    pub max_parse_response_bytes_count: Option<u8>,
+    pub stream_credits: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl AsyncTransportConfigurationData {
+    pub fn into_configuration<H>(
+        self,
+        response_handler: H,
+    ) -> Result<AsyncTransportConfiguration<H>, ConfigError>
+    where
+        H: ResponseHandlerV2,
+    {
+        let mut builder = AsyncTransportConfiguration::builder(response_handler);
+        if let Some(size) = self.buf_size {
+            builder = builder.buf_size(size);
+        }
+        if let Some(size) = self.max_buf_size {
+            builder = builder.max_buf_size(size);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.write_timeout {
+            builder = builder.write_timeout(timeout);
+        }
+        if let Some(count) = self.max_parse_response_bytes_count {
+            builder = builder.max_parse_response_bytes_count(count);
+        }
+        if let Some(credits) = self.stream_credits {
+            builder = builder.stream_credits(credits);
+        }
+        builder.build()
+    }
 }
 
 #[cfg(test)]
@@ -106,11 +343,105 @@ mod tests {
         assert_eq!(c.get_buf_size(), 1024 * 2);
         c.set_max_buf_size(1024 * 3);
         assert_eq!(c.get_max_buf_size(), 1024 * 3);
-        c.set_read_timeout(3000);
+        c.set_read_timeout(Duration::from_secs(3));
         assert_eq!(c.get_read_timeout(), Duration::from_secs(3));
+        assert_eq!(c.get_write_timeout(), Duration::from_secs(5));
+        c.set_write_timeout(Duration::from_secs(2));
+        assert_eq!(c.get_write_timeout(), Duration::from_secs(2));
         c.set_max_parse_response_bytes_count(2);
         assert_eq!(c.get_max_parse_response_bytes_count(), 2);
+        assert_eq!(c.get_stream_credits(), 0);
+        c.set_stream_credits(16);
+        assert_eq!(c.get_stream_credits(), 16);
 
         println!("{c:?}");
     }
+
+    #[test]
+    fn test_builder() {
+        let c = AsyncTransportConfiguration::builder(MockResponseHandler)
+            .buf_size(1024 * 2)
+            .max_buf_size(1024 * 8)
+            .read_timeout(Duration::from_millis(500))
+            .max_parse_response_bytes_count(5)
+            .build()
+            .unwrap();
+        assert_eq!(c.get_buf_size(), 1024 * 2);
+        assert_eq!(c.get_max_buf_size(), 1024 * 8);
+        assert_eq!(c.get_read_timeout(), Duration::from_millis(500));
+        assert_eq!(c.get_max_parse_response_bytes_count(), 5);
+
+        assert_eq!(
+            AsyncTransportConfiguration::builder(MockResponseHandler)
+                .buf_size(1024 * 8)
+                .build()
+                .unwrap_err(),
+            ConfigError::BufSizeExceedsMaxBufSize {
+                buf_size: 1024 * 8,
+                max_buf_size: 1024 * 4
+            }
+        );
+        assert_eq!(
+            AsyncTransportConfiguration::builder(MockResponseHandler)
+                .buf_size(0)
+                .build()
+                .unwrap_err(),
+            ConfigError::ZeroBufSize
+        );
+        assert_eq!(
+            AsyncTransportConfiguration::builder(MockResponseHandler)
+                .read_timeout(Duration::ZERO)
+                .build()
+                .unwrap_err(),
+            ConfigError::ZeroReadTimeout
+        );
+        assert_eq!(
+            AsyncTransportConfiguration::builder(MockResponseHandler)
+                .write_timeout(Duration::ZERO)
+                .build()
+                .unwrap_err(),
+            ConfigError::ZeroWriteTimeout
+        );
+        assert_eq!(
+            AsyncTransportConfiguration::builder(MockResponseHandler)
+                .max_parse_response_bytes_count(0)
+                .build()
+                .unwrap_err(),
+            ConfigError::ZeroMaxParseResponseBytesCount
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_from_data() {
+        let data: AsyncTransportConfigurationData = toml::from_str(
+            r#"
+buf_size = 2048
+max_buf_size = 8192
+read_timeout = "1s 500ms"
+write_timeout = "2s"
+"#,
+        )
+        .unwrap();
+        assert_eq!(data.read_timeout, Some(Duration::from_millis(1500)));
+
+        let c = data.into_configuration(MockResponseHandler).unwrap();
+        assert_eq!(c.get_buf_size(), 2048);
+        assert_eq!(c.get_max_buf_size(), 8192);
+        assert_eq!(c.get_read_timeout(), Duration::from_millis(1500));
+        assert_eq!(c.get_write_timeout(), Duration::from_secs(2));
+        assert_eq!(c.get_max_parse_response_bytes_count(), 3);
+
+        //
+        let data: AsyncTransportConfigurationData = toml::from_str("").unwrap();
+        assert_eq!(data, AsyncTransportConfigurationData::default());
+
+        let data: AsyncTransportConfigurationData = toml::from_str("buf_size = 8192").unwrap();
+        assert!(matches!(
+            data.into_configuration(MockResponseHandler),
+            Err(ConfigError::BufSizeExceedsMaxBufSize { .. })
+        ));
+
+        assert!(toml::from_str::<AsyncTransportConfigurationData>("foo = 1").is_err());
+    }
 }
@@ -0,0 +1,19 @@
+use core::future::Future;
+use std::io::Error as IoError;
+
+use futures_util::future::BoxFuture;
+
+//
+pub trait Connector<S>: Send + Sync {
+    fn connect(&self) -> BoxFuture<'static, Result<S, IoError>>;
+}
+
+impl<S, F, Fut> Connector<S> for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<S, IoError>> + Send + 'static,
+{
+    fn connect(&self) -> BoxFuture<'static, Result<S, IoError>> {
+        Box::pin(self())
+    }
+}
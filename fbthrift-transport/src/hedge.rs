@@ -0,0 +1,470 @@
+use core::{
+    ffi::CStr,
+    future::Future,
+    pin::{pin, Pin},
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::{
+    collections::VecDeque,
+    io::{Cursor, Error as IoError, ErrorKind as IoErrorKind},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use async_lock::{Mutex as AsyncMutex, MutexGuardArc};
+use async_sleep::Sleepble;
+use bytes::{Bytes, BytesMut};
+use fbthrift::{Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use fbthrift_transport_response_handler::ResponseHandlerV2;
+use futures_util::{
+    future::{self, join_all, BoxFuture, Either},
+    io::{AsyncRead, AsyncWrite},
+    ready,
+};
+
+use crate::{
+    configuration::AsyncTransportConfiguration,
+    connector::Connector,
+    idempotency::Idempotency,
+    stats::TransportStats,
+    transport::{AsyncTransport, AsyncTransportRpcOptions},
+};
+
+//
+#[derive(Debug, Clone)]
+pub struct HedgePolicy {
+    percentile: f64,
+    min_delay: Duration,
+    max_delay: Duration,
+    window_size: usize,
+    min_samples: usize,
+    idempotency: Idempotency,
+}
+
+impl Default for HedgePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HedgePolicy {
+    pub fn new() -> Self {
+        Self {
+            percentile: 0.95,
+            min_delay: Duration::from_millis(5),
+            max_delay: Duration::from_secs(1),
+            window_size: 100,
+            min_samples: 10,
+            idempotency: Idempotency::new(),
+        }
+    }
+
+    pub fn set_percentile(&mut self, percentile: f64) {
+        debug_assert!(percentile > 0.0 && percentile <= 1.0);
+        self.percentile = percentile;
+    }
+
+    pub fn get_percentile(&self) -> f64 {
+        self.percentile
+    }
+
+    pub fn set_min_delay(&mut self, dur: Duration) {
+        debug_assert!(dur <= self.max_delay);
+        self.min_delay = dur;
+    }
+
+    pub fn get_min_delay(&self) -> Duration {
+        self.min_delay
+    }
+
+    // Also used as the delay until `min_samples` latencies are recorded.
+    pub fn set_max_delay(&mut self, dur: Duration) {
+        debug_assert!(dur >= self.min_delay);
+        self.max_delay = dur;
+    }
+
+    pub fn get_max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    pub fn set_window_size(&mut self, n: usize) {
+        debug_assert!(n > 0 && n >= self.min_samples);
+        self.window_size = n;
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.window_size
+    }
+
+    pub fn set_min_samples(&mut self, n: usize) {
+        debug_assert!(n <= self.window_size);
+        self.min_samples = n;
+    }
+
+    pub fn get_min_samples(&self) -> usize {
+        self.min_samples
+    }
+
+    // Only the idempotent functions are hedged.
+    pub fn set_idempotency(&mut self, idempotency: Idempotency) {
+        self.idempotency = idempotency;
+    }
+
+    pub fn get_idempotency(&self) -> &Idempotency {
+        &self.idempotency
+    }
+
+    fn delay(&self, latencies: &VecDeque<Duration>) -> Duration {
+        if latencies.is_empty() || latencies.len() < self.min_samples {
+            return self.max_delay;
+        }
+
+        let mut sorted = latencies.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let i = ((sorted.len() - 1) as f64 * self.percentile).round() as usize;
+
+        sorted[i].clamp(self.min_delay, self.max_delay)
+    }
+}
+
+//
+// With the latency of the call, its reconnect included, without the wait for the connection.
+type PendingCall = BoxFuture<'static, anyhow::Result<(Cursor<Bytes>, Duration)>>;
+
+// One call at a time per connection. A call that is dropped before completing (the loser of a
+// hedge, or a cancelled call) leaves its response unread, so the next call on the connection
+// reconnects first.
+struct ConnectionCall<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    transport: Arc<AsyncTransport<S, SLEEP, H>>,
+    _slot: MutexGuardArc<()>,
+    call: Option<PendingCall>,
+}
+
+impl<S, SLEEP, H> Future for ConnectionCall<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    type Output = anyhow::Result<(Cursor<Bytes>, Duration)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let call = match this.call.as_mut() {
+            Some(call) => call,
+            None => return Poll::Ready(Err(IoError::other("Polled after completion").into())),
+        };
+        let ret = ready!(call.as_mut().poll(cx));
+        this.call = None;
+
+        Poll::Ready(ret)
+    }
+}
+
+impl<S, SLEEP, H> Drop for ConnectionCall<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn drop(&mut self) {
+        if self.call.take().is_some() {
+            self.transport.desync();
+        }
+    }
+}
+
+struct Connection<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    transport: Arc<AsyncTransport<S, SLEEP, H>>,
+    slot: Arc<AsyncMutex<()>>,
+}
+
+//
+struct Inner<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    connections: Vec<Connection<S, SLEEP, H>>,
+    policy: HedgePolicy,
+    latencies: Mutex<VecDeque<Duration>>,
+    next: AtomicUsize,
+}
+
+impl<S, SLEEP, H> Inner<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    SLEEP: Sleepble + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
+{
+    fn try_acquire_idle(
+        &self,
+        start: usize,
+        except: Option<usize>,
+    ) -> Option<(usize, MutexGuardArc<()>)> {
+        let n = self.connections.len();
+        (0..n)
+            .map(|k| (start + k) % n)
+            .filter(|i| Some(*i) != except)
+            .find_map(|i| Some((i, self.connections[i].slot.try_lock_arc()?)))
+    }
+
+    async fn acquire(&self) -> (usize, MutexGuardArc<()>) {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len();
+        if let Some(acquired) = self.try_acquire_idle(start, None) {
+            return acquired;
+        }
+        (start, self.connections[start].slot.lock_arc().await)
+    }
+
+    fn start(
+        &self,
+        i: usize,
+        slot: MutexGuardArc<()>,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: Bytes,
+        rpc_options: AsyncTransportRpcOptions,
+    ) -> ConnectionCall<S, SLEEP, H> {
+        let transport = self.connections[i].transport.clone();
+
+        let call = transport.call(service_name, fn_name, req, rpc_options);
+        let call = Box::pin(async move {
+            let started_at = Instant::now();
+            let cursor = call.await?;
+            Ok((cursor, started_at.elapsed()))
+        });
+
+        ConnectionCall {
+            transport,
+            _slot: slot,
+            call: Some(call),
+        }
+    }
+
+    fn delay(&self) -> Duration {
+        match self.latencies.lock() {
+            Ok(latencies) => self.policy.delay(&latencies),
+            Err(_) => self.policy.max_delay,
+        }
+    }
+
+    fn record(&self, latency: Duration) {
+        if let Ok(mut latencies) = self.latencies.lock() {
+            latencies.push_back(latency);
+            while latencies.len() > self.policy.window_size {
+                latencies.pop_front();
+            }
+        }
+    }
+}
+
+//
+pub struct HedgeTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    inner: Arc<Inner<S, SLEEP, H>>,
+}
+
+impl<S, SLEEP, H> HedgeTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    SLEEP: Sleepble + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
+{
+    pub async fn new(
+        connectors: Vec<Arc<dyn Connector<S>>>,
+        configuration: AsyncTransportConfiguration<H>,
+        policy: HedgePolicy,
+    ) -> Result<Self, IoError> {
+        if connectors.len() < 2 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Hedging requires at least 2 connectors",
+            ));
+        }
+
+        let mut connections = Vec::with_capacity(connectors.len());
+        for connector in connectors {
+            let transport =
+                AsyncTransport::with_connector(connector, configuration.clone()).await?;
+            connections.push(Connection {
+                transport: Arc::new(transport),
+                slot: Arc::new(AsyncMutex::new(())),
+            });
+        }
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                connections,
+                policy,
+                latencies: Mutex::new(VecDeque::new()),
+                next: AtomicUsize::new(0),
+            }),
+        })
+    }
+
+    pub fn get_policy(&self) -> &HedgePolicy {
+        &self.inner.policy
+    }
+
+    pub fn connections_len(&self) -> usize {
+        self.inner.connections.len()
+    }
+
+    pub fn hedge_delay(&self) -> Duration {
+        self.inner.delay()
+    }
+
+    // In connections order.
+    pub fn connections_stats(&self) -> Vec<TransportStats> {
+        self.inner
+            .connections
+            .iter()
+            .map(|connection| connection.transport.stats())
+            .collect()
+    }
+
+    // Closes every connection, see `AsyncTransport::close`. Fails with the first error.
+    pub async fn close(&self, timeout: Duration) -> Result<(), IoError> {
+        join_all(
+            self.inner
+                .connections
+                .iter()
+                .map(|connection| connection.transport.close(timeout)),
+        )
+        .await
+        .into_iter()
+        .collect()
+    }
+}
+
+impl<S, SLEEP, H> Framing for HedgeTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        Self::EncBuf::with_capacity(cap)
+    }
+}
+
+impl<S, SLEEP, H> Transport for HedgeTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    SLEEP: Sleepble + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
+{
+    type RpcOptions = AsyncTransportRpcOptions;
+
+    fn call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let inner = self.inner.clone();
+
+        Box::pin(async move {
+            let (i, slot) = inner.acquire().await;
+            let mut primary = inner.start(
+                i,
+                slot,
+                service_name,
+                fn_name,
+                req.clone(),
+                rpc_options.clone(),
+            );
+
+            let ret = 'ret: {
+                if !inner
+                    .policy
+                    .idempotency
+                    .is_idempotent(service_name, fn_name)
+                {
+                    break 'ret (&mut primary).await;
+                }
+
+                let sleep = pin!(async_sleep::sleep::<SLEEP>(inner.delay()));
+                if let Either::Left((ret, _)) = future::select(&mut primary, sleep).await {
+                    break 'ret ret;
+                }
+
+                let (j, slot) = match inner.try_acquire_idle(i + 1, Some(i)) {
+                    Some(acquired) => acquired,
+                    None => break 'ret (&mut primary).await,
+                };
+                let mut hedged = inner.start(j, slot, service_name, fn_name, req, rpc_options);
+
+                // First successful reply wins, the loser is dropped.
+                match future::select(&mut primary, &mut hedged).await {
+                    Either::Left((Ok(out), _)) | Either::Right((Ok(out), _)) => Ok(out),
+                    Either::Left((Err(_), _)) => hedged.await,
+                    Either::Right((Err(_), _)) => primary.await,
+                }
+            };
+
+            ret.map(|(cursor, latency)| {
+                inner.record(latency);
+                cursor
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_delay() {
+        let mut p = HedgePolicy::new();
+        p.set_min_delay(Duration::from_millis(10));
+        p.set_max_delay(Duration::from_millis(500));
+        p.set_min_samples(5);
+        p.set_percentile(0.9);
+
+        let mut latencies = VecDeque::new();
+        assert_eq!(p.delay(&latencies), Duration::from_millis(500));
+
+        latencies.extend((1..=4).map(|i| Duration::from_millis(i * 20)));
+        assert_eq!(p.delay(&latencies), Duration::from_millis(500));
+
+        latencies.extend((5..=11).map(|i| Duration::from_millis(i * 20)));
+        assert_eq!(p.delay(&latencies), Duration::from_millis(200));
+
+        latencies
+            .iter_mut()
+            .for_each(|x| *x = Duration::from_millis(1));
+        assert_eq!(p.delay(&latencies), Duration::from_millis(10));
+
+        latencies
+            .iter_mut()
+            .for_each(|x| *x = Duration::from_secs(1));
+        assert_eq!(p.delay(&latencies), Duration::from_millis(500));
+    }
+}
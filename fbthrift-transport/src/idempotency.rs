@@ -0,0 +1,66 @@
+use core::ffi::CStr;
+use std::{collections::HashSet, sync::Arc};
+
+//
+pub type IdempotentPredicate = Arc<dyn Fn(&CStr, &CStr) -> bool + Send + Sync>;
+
+// The functions safe to send more than once, by retries or hedging.
+#[derive(Clone, Default)]
+pub struct Idempotency {
+    fns: HashSet<(Vec<u8>, Vec<u8>)>,
+    predicate: Option<IdempotentPredicate>,
+}
+
+impl core::fmt::Debug for Idempotency {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Idempotency")
+            .field("fns", &self.fns)
+            .field("predicate", &self.predicate.is_some())
+            .finish()
+    }
+}
+
+impl Idempotency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_idempotent_fn(&mut self, service_name: &str, fn_name: &str) {
+        self.fns.insert((service_name.into(), fn_name.into()));
+    }
+
+    pub fn set_idempotent_predicate(&mut self, predicate: IdempotentPredicate) {
+        self.predicate = Some(predicate);
+    }
+
+    pub fn is_idempotent(&self, service_name: &CStr, fn_name: &CStr) -> bool {
+        self.fns
+            .contains(&(service_name.to_bytes().into(), fn_name.to_bytes().into()))
+            || self
+                .predicate
+                .as_ref()
+                .map(|predicate| predicate(service_name, fn_name))
+                .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_idempotent() {
+        let mut idempotency = Idempotency::new();
+        assert!(!idempotency.is_idempotent(c"my_service", c"get"));
+
+        idempotency.add_idempotent_fn("my_service", "get");
+        idempotency.set_idempotent_predicate(Arc::new(|_, fn_name| {
+            fn_name.to_bytes().starts_with(b"list")
+        }));
+
+        assert!(idempotency.is_idempotent(c"my_service", c"get"));
+        assert!(idempotency.is_idempotent(c"my_service", c"list_foo"));
+        assert!(!idempotency.is_idempotent(c"my_service", c"set"));
+        assert!(!idempotency.is_idempotent(c"other_service", c"get"));
+    }
+}
@@ -1,5 +1,7 @@
 use std::io::Error as IoError;
 
+use crate::connector::Connector;
+
 //
 pub type AsyncIoTcpStream = async_io::Async<std::net::TcpStream>;
 pub type AsyncIoSleep = async_sleep::impl_async_io::Timer;
@@ -10,3 +12,7 @@ pub async fn tcp_connect<A: Into<std::net::SocketAddr>>(
 ) -> Result<AsyncIoTcpStream, IoError> {
     AsyncIoTcpStream::connect(addr).await
 }
+
+pub fn tcp_connector(addr: std::net::SocketAddr) -> impl Connector<AsyncIoTcpStream> {
+    move || tcp_connect(addr)
+}
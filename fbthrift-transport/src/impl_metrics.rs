@@ -0,0 +1,200 @@
+use core::{ffi::CStr, time::Duration};
+
+use crate::observer::{CallMetrics, CallObserver};
+
+//
+#[derive(Debug, Clone)]
+pub struct MetricsCallObserver {
+    prefix: &'static str,
+}
+
+impl Default for MetricsCallObserver {
+    fn default() -> Self {
+        Self::new("fbthrift_transport")
+    }
+}
+
+impl MetricsCallObserver {
+    pub fn new(prefix: &'static str) -> Self {
+        Self { prefix }
+    }
+
+    fn name(&self, suffix: &str) -> String {
+        format!("{}_{}", self.prefix, suffix)
+    }
+}
+
+impl CallObserver for MetricsCallObserver {
+    fn on_write_done(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        _request_size: usize,
+        elapsed: Duration,
+    ) {
+        metrics::histogram!(
+            self.name("write_seconds"),
+            "service_name" => service_name.to_string_lossy().into_owned(),
+            "fn_name" => fn_name.to_string_lossy().into_owned(),
+        )
+        .record(elapsed.as_secs_f64());
+    }
+
+    fn on_first_byte(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        elapsed: Duration,
+    ) {
+        metrics::histogram!(
+            self.name("first_byte_seconds"),
+            "service_name" => service_name.to_string_lossy().into_owned(),
+            "fn_name" => fn_name.to_string_lossy().into_owned(),
+        )
+        .record(elapsed.as_secs_f64());
+    }
+
+    fn on_complete(&self, m: &CallMetrics) {
+        let service_name = m.service_name.to_string_lossy().into_owned();
+        let fn_name = m.fn_name.to_string_lossy().into_owned();
+
+        metrics::counter!(
+            self.name("calls_total"),
+            "service_name" => service_name.clone(),
+            "fn_name" => fn_name.clone(),
+            "outcome" => m.outcome.as_str(),
+        )
+        .increment(1);
+        metrics::histogram!(
+            self.name("latency_seconds"),
+            "service_name" => service_name.clone(),
+            "fn_name" => fn_name.clone(),
+        )
+        .record(m.latency.as_secs_f64());
+        metrics::histogram!(
+            self.name("request_bytes"),
+            "service_name" => service_name.clone(),
+            "fn_name" => fn_name.clone(),
+        )
+        .record(m.request_size as f64);
+        metrics::histogram!(
+            self.name("response_bytes"),
+            "service_name" => service_name.clone(),
+            "fn_name" => fn_name.clone(),
+        )
+        .record(m.response_size as f64);
+        // Each attempt completes on its own, `retry_count` being its number among the retries.
+        if m.retry_count > 0 {
+            metrics::counter!(
+                self.name("retries_total"),
+                "service_name" => service_name,
+                "fn_name" => fn_name,
+            )
+            .increment(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    };
+
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+
+    use crate::observer::CallOutcome;
+
+    #[derive(Default)]
+    struct FooRecorder {
+        counters: Mutex<Vec<(String, Arc<AtomicU64>)>>,
+    }
+
+    struct FooCounter(Arc<AtomicU64>);
+
+    impl CounterFn for FooCounter {
+        fn increment(&self, value: u64) {
+            self.0.fetch_add(value, Ordering::SeqCst);
+        }
+
+        fn absolute(&self, value: u64) {
+            self.0.store(value, Ordering::SeqCst);
+        }
+    }
+
+    impl FooRecorder {
+        fn counter_value(&self, name: &str) -> u64 {
+            self.counters
+                .lock()
+                .expect("")
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(_, value)| value.load(Ordering::SeqCst))
+                .sum()
+        }
+    }
+
+    impl Recorder for FooRecorder {
+        fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
+        }
+
+        fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+        fn describe_histogram(
+            &self,
+            _key: KeyName,
+            _unit: Option<Unit>,
+            _description: SharedString,
+        ) {
+        }
+
+        fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+            let value = Arc::new(AtomicU64::new(0));
+            self.counters
+                .lock()
+                .expect("")
+                .push((key.name().to_owned(), value.clone()));
+            Counter::from_arc(Arc::new(FooCounter(value)))
+        }
+
+        fn register_gauge(&self, _key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn test_retries_total() {
+        let recorder = FooRecorder::default();
+        let observer = MetricsCallObserver::default();
+
+        metrics::with_local_recorder(&recorder, || {
+            // The first attempt and 2 retries.
+            for retry_count in 0..3 {
+                observer.on_complete(&CallMetrics {
+                    service_name: c"my_service",
+                    fn_name: c"my_fn",
+                    latency: Duration::from_millis(1),
+                    request_size: 1,
+                    response_size: 0,
+                    outcome: CallOutcome::Failure,
+                    retry_count,
+                });
+            }
+        });
+
+        assert_eq!(recorder.counter_value("fbthrift_transport_calls_total"), 3);
+        assert_eq!(
+            recorder.counter_value("fbthrift_transport_retries_total"),
+            2
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::io::Error as IoError;
 
+use crate::connector::Connector;
+
 //
 pub type TokioTcpStream = async_compat::Compat<tokio::net::TcpStream>;
 pub type TokioSleep = async_sleep::impl_tokio::Sleep;
@@ -10,3 +12,7 @@ pub async fn tcp_connect<A: tokio::net::ToSocketAddrs>(addr: A) -> Result<TokioT
         .await
         .map(async_compat::Compat::new)
 }
+
+pub fn tcp_connector(addr: std::net::SocketAddr) -> impl Connector<TokioTcpStream> {
+    move || tcp_connect(addr)
+}
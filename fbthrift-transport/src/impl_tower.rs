@@ -0,0 +1,152 @@
+use core::{
+    ffi::CStr,
+    task::{Context, Poll},
+};
+use std::{
+    io::{Cursor, Error as IoError},
+    sync::Arc,
+};
+
+use bytes::{Bytes, BytesMut};
+use fbthrift::{Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use futures_util::future::{self, BoxFuture, FutureExt as _};
+use tower::{BoxError, Service};
+
+use crate::transport::AsyncTransportRpcOptions;
+
+//
+#[derive(Debug, Clone)]
+pub struct ThriftRequest<Req = Bytes, O = AsyncTransportRpcOptions> {
+    pub service_name: &'static CStr,
+    pub fn_name: &'static CStr,
+    pub req: Req,
+    pub rpc_options: O,
+}
+
+//
+// fbthrift::Transport -> tower::Service
+//
+pub struct TransportService<T> {
+    transport: Arc<T>,
+}
+
+impl<T> Clone for TransportService<T> {
+    fn clone(&self) -> Self {
+        Self {
+            transport: self.transport.clone(),
+        }
+    }
+}
+
+impl<T> TransportService<T> {
+    pub fn new(transport: T) -> Self {
+        Self::with_arc(Arc::new(transport))
+    }
+
+    pub fn with_arc(transport: Arc<T>) -> Self {
+        Self { transport }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+}
+
+impl<T> Service<ThriftRequest<FramingEncodedFinal<T>, T::RpcOptions>> for TransportService<T>
+where
+    T: Transport,
+{
+    type Response = FramingDecoded<T>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    // Always ready, calls are not queued here, see `AsyncTransport` for the calls sharing a
+    // connection.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ThriftRequest<FramingEncodedFinal<T>, T::RpcOptions>) -> Self::Future {
+        self.transport
+            .call(req.service_name, req.fn_name, req.req, req.rpc_options)
+            .map(|ret| ret.map_err(|err| err.reallocate_into_boxed_dyn_error_without_backtrace()))
+            .boxed()
+    }
+}
+
+//
+// tower::Service -> fbthrift::Transport
+//
+pub struct ServiceTransport<Svc, O = AsyncTransportRpcOptions> {
+    service: Svc,
+    phantom: core::marker::PhantomData<fn() -> O>,
+}
+
+impl<Svc, O> ServiceTransport<Svc, O> {
+    pub fn new(service: Svc) -> Self {
+        Self {
+            service,
+            phantom: core::marker::PhantomData,
+        }
+    }
+
+    pub fn get_ref(&self) -> &Svc {
+        &self.service
+    }
+}
+
+impl<Svc, O> Framing for ServiceTransport<Svc, O> {
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        Self::EncBuf::with_capacity(cap)
+    }
+}
+
+impl<Svc, O> Transport for ServiceTransport<Svc, O>
+where
+    Svc: Service<ThriftRequest<Bytes, O>, Response = Cursor<Bytes>> + Clone + Send + Sync + 'static,
+    Svc::Error: Into<BoxError>,
+    Svc::Future: Send + 'static,
+    // Required by `Transport::RpcOptions`.
+    O: Default + Send + 'static,
+{
+    type RpcOptions = O;
+
+    fn call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let mut service = self.service.clone();
+        let req = ThriftRequest {
+            service_name,
+            fn_name,
+            req,
+            rpc_options,
+        };
+
+        async move {
+            future::poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(|err| box_error_to_anyhow(err.into()))?;
+
+            service
+                .call(req)
+                .await
+                .map_err(|err| box_error_to_anyhow(err.into()))
+        }
+        .boxed()
+    }
+}
+
+// Keep `std::io::Error` downcastable, callers match on its kind.
+fn box_error_to_anyhow(err: BoxError) -> anyhow::Error {
+    match err.downcast::<IoError>() {
+        Ok(err) => (*err).into(),
+        Err(err) => anyhow::Error::from_boxed(err),
+    }
+}
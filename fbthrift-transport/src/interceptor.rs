@@ -0,0 +1,25 @@
+use core::ffi::CStr;
+
+use bytes::Bytes;
+
+//
+pub trait Interceptor: Send + Sync {
+    fn before_call(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        request_bytes: Bytes,
+    ) -> Result<Bytes, anyhow::Error> {
+        Ok(request_bytes)
+    }
+
+    fn after_call(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        _request_bytes: &Bytes,
+        result: Result<Bytes, anyhow::Error>,
+    ) -> Result<Bytes, anyhow::Error> {
+        result
+    }
+}
@@ -2,7 +2,35 @@ pub use fbthrift_transport_response_handler;
 
 //
 pub mod configuration;
-pub use configuration::AsyncTransportConfiguration;
+#[cfg(feature = "serde")]
+pub use configuration::AsyncTransportConfigurationData;
+pub use configuration::{
+    AsyncTransportConfiguration, AsyncTransportConfigurationBuilder, ConfigError,
+};
+
+//
+pub mod balance;
+pub use balance::{BalanceStrategy, BalancedTransport};
+
+//
+pub mod buffer_pool;
+pub use buffer_pool::BufferPool;
+
+//
+pub mod circuit_breaker;
+pub use circuit_breaker::{CircuitBreakerConfiguration, CircuitBreakerTransport};
+
+//
+pub mod connector;
+pub use connector::Connector;
+
+//
+pub mod hedge;
+pub use hedge::{HedgePolicy, HedgeTransport};
+
+//
+pub mod idempotency;
+pub use idempotency::Idempotency;
 
 //
 #[cfg(feature = "impl_async_io")]
@@ -10,6 +38,44 @@ pub mod impl_async_io;
 #[cfg(feature = "impl_tokio")]
 pub mod impl_tokio;
 
+//
+pub mod interceptor;
+pub use interceptor::Interceptor;
+
+//
+pub mod limit;
+pub use limit::{LimitConfiguration, LimitTransport};
+
+//
+pub mod observer;
+pub use observer::{CallMetrics, CallObserver, CallOutcome};
+#[cfg(feature = "metrics")]
+pub mod impl_metrics;
+
+//
+#[cfg(feature = "tower")]
+pub mod impl_tower;
+
+//
+pub mod retry;
+pub use retry::{RetryBudget, RetryPolicy, RetryTransport};
+
+//
+pub mod sink;
+pub use sink::CallSink;
+
+//
+pub mod stats;
+pub use stats::TransportStats;
+
+//
+pub mod stream;
+pub use stream::CallStream;
+
+//
+pub mod wire_dump;
+pub use wire_dump::WireDump;
+
 //
 pub mod transport;
 pub use transport::AsyncTransport;
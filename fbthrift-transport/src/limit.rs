@@ -0,0 +1,349 @@
+use core::{ffi::CStr, marker::PhantomData, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use async_lock::{Semaphore, SemaphoreGuardArc};
+use async_sleep::Sleepble;
+use fbthrift::{ClientStreamElement, Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use futures_util::{
+    future::{self, BoxFuture, Either},
+    stream::BoxStream,
+    StreamExt as _,
+};
+
+//
+#[derive(Debug, Clone)]
+pub struct LimitConfiguration {
+    rate_limit: Option<(f64, u32)>,
+    max_in_flight: Option<usize>,
+    max_queue: usize,
+    queue_timeout: Duration,
+}
+
+impl Default for LimitConfiguration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LimitConfiguration {
+    pub fn new() -> Self {
+        Self {
+            rate_limit: None,
+            max_in_flight: None,
+            max_queue: 0,
+            queue_timeout: Duration::from_secs(1),
+        }
+    }
+
+    pub fn set_rate_limit(&mut self, per_second: f64, burst: u32) {
+        debug_assert!(per_second > 0.0);
+        debug_assert!(burst > 0);
+        self.rate_limit = Some((per_second, burst));
+    }
+
+    pub fn get_rate_limit(&self) -> Option<(f64, u32)> {
+        self.rate_limit
+    }
+
+    // Above 1, the calls only run concurrently on an inner transport with several connections, see
+    // `AsyncTransport`.
+    pub fn set_max_in_flight(&mut self, n: usize) {
+        debug_assert!(n > 0);
+        self.max_in_flight = Some(n);
+    }
+
+    pub fn get_max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+
+    // 0 means rejecting immediately instead of waiting.
+    pub fn set_max_queue(&mut self, n: usize) {
+        self.max_queue = n;
+    }
+
+    pub fn get_max_queue(&self) -> usize {
+        self.max_queue
+    }
+
+    pub fn set_queue_timeout(&mut self, dur: Duration) {
+        self.queue_timeout = dur;
+    }
+
+    pub fn get_queue_timeout(&self) -> Duration {
+        self.queue_timeout
+    }
+}
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadError {
+    RateLimited,
+    TooManyInFlight,
+    QueueFull,
+    QueueTimeout,
+}
+
+impl core::fmt::Display for OverloadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::RateLimited => write!(f, "Overloaded: rate limited"),
+            Self::TooManyInFlight => write!(f, "Overloaded: too many in-flight calls"),
+            Self::QueueFull => write!(f, "Overloaded: queue full"),
+            Self::QueueTimeout => write!(f, "Overloaded: queue timeout"),
+        }
+    }
+}
+
+impl std::error::Error for OverloadError {}
+
+//
+#[derive(Debug)]
+struct TokenBucket {
+    per_second: f64,
+    burst: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(per_second: f64, burst: u32) -> Self {
+        Self {
+            per_second,
+            burst: burst as f64,
+            state: Mutex::new((burst as f64, Instant::now())),
+        }
+    }
+
+    // Takes one token, tokens may go negative, returns how long to wait for it.
+    fn reserve(&self) -> Duration {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(err) => err.into_inner(),
+        };
+        let now = Instant::now();
+        let (tokens, last) = *state;
+        let tokens = (tokens + now.duration_since(last).as_secs_f64() * self.per_second)
+            .min(self.burst)
+            - 1.0;
+        *state = (tokens, now);
+
+        if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / self.per_second)
+        }
+    }
+
+    fn cancel(&self) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(err) => err.into_inner(),
+        };
+        state.0 += 1.0;
+    }
+}
+
+// Gives the reserved token back unless disarmed, also when the caller is dropped while waiting.
+struct ReservationGuard<'a>(Option<&'a TokenBucket>);
+
+impl Drop for ReservationGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(bucket) = self.0 {
+            bucket.cancel();
+        }
+    }
+}
+
+struct QueueGuard<'a>(&'a AtomicUsize);
+
+impl<'a> QueueGuard<'a> {
+    fn enter(queued: &'a AtomicUsize, max_queue: usize) -> Result<Self, OverloadError> {
+        queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max_queue).then_some(n + 1)
+            })
+            .map(|_| Self(queued))
+            .map_err(|_| OverloadError::QueueFull)
+    }
+}
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+struct Limiter {
+    configuration: LimitConfiguration,
+    bucket: Option<TokenBucket>,
+    semaphore: Option<Arc<Semaphore>>,
+    queued: AtomicUsize,
+}
+
+impl Limiter {
+    async fn acquire<SLEEP: Sleepble>(&self) -> Result<Option<SemaphoreGuardArc>, OverloadError> {
+        let max_queue = self.configuration.max_queue;
+        let deadline = Instant::now() + self.configuration.queue_timeout;
+
+        if let Some(bucket) = &self.bucket {
+            let wait = bucket.reserve();
+            if !wait.is_zero() {
+                let mut reservation_guard = ReservationGuard(Some(bucket));
+                if max_queue == 0 || wait > self.configuration.queue_timeout {
+                    return Err(OverloadError::RateLimited);
+                }
+                let _queue_guard = QueueGuard::enter(&self.queued, max_queue)?;
+                async_sleep::sleep::<SLEEP>(wait).await;
+                reservation_guard.0 = None;
+            }
+        }
+
+        if let Some(semaphore) = &self.semaphore {
+            if let Some(guard) = semaphore.try_acquire_arc() {
+                return Ok(Some(guard));
+            }
+            if max_queue == 0 {
+                return Err(OverloadError::TooManyInFlight);
+            }
+
+            let _queue_guard = QueueGuard::enter(&self.queued, max_queue)?;
+            let acquire = core::pin::pin!(semaphore.acquire_arc());
+            let sleep = core::pin::pin!(async_sleep::sleep_until::<SLEEP>(deadline));
+            return match future::select(acquire, sleep).await {
+                Either::Left((guard, _)) => Ok(Some(guard)),
+                Either::Right(_) => Err(OverloadError::QueueTimeout),
+            };
+        }
+
+        Ok(None)
+    }
+}
+
+//
+pub struct LimitTransport<T, SLEEP> {
+    transport: Arc<T>,
+    limiter: Arc<Limiter>,
+    phantom: PhantomData<fn() -> SLEEP>,
+}
+
+impl<T, SLEEP> LimitTransport<T, SLEEP> {
+    pub fn new(transport: T, configuration: LimitConfiguration) -> Self {
+        let bucket = configuration
+            .rate_limit
+            .map(|(per_second, burst)| TokenBucket::new(per_second, burst));
+        let semaphore = configuration
+            .max_in_flight
+            .map(|n| Arc::new(Semaphore::new(n)));
+
+        Self {
+            transport: Arc::new(transport),
+            limiter: Arc::new(Limiter {
+                configuration,
+                bucket,
+                semaphore,
+                queued: AtomicUsize::new(0),
+            }),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_configuration(&self) -> &LimitConfiguration {
+        &self.limiter.configuration
+    }
+
+    pub fn queued(&self) -> usize {
+        self.limiter.queued.load(Ordering::Acquire)
+    }
+}
+
+impl<T, SLEEP> Framing for LimitTransport<T, SLEEP>
+where
+    T: Framing,
+{
+    type EncBuf = T::EncBuf;
+    type DecBuf = T::DecBuf;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        T::enc_with_capacity(cap)
+    }
+}
+
+impl<T, SLEEP> Transport for LimitTransport<T, SLEEP>
+where
+    T: Transport,
+    T::RpcOptions: Send,
+    FramingEncodedFinal<T>: Send,
+    SLEEP: Sleepble + 'static,
+{
+    type RpcOptions = T::RpcOptions;
+
+    fn call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let transport = self.transport.clone();
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let _permit = limiter.acquire::<SLEEP>().await?;
+
+            transport
+                .call(service_name, fn_name, req, rpc_options)
+                .await
+        })
+    }
+
+    // The in-flight permit is held until the stream is dropped.
+    fn call_stream(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<
+        'static,
+        anyhow::Result<(
+            FramingDecoded<Self>,
+            BoxStream<'static, anyhow::Result<ClientStreamElement<FramingDecoded<Self>>>>,
+        )>,
+    > {
+        let transport = self.transport.clone();
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let permit = limiter.acquire::<SLEEP>().await?;
+
+            let (res, stream) = transport
+                .call_stream(service_name, fn_name, req, rpc_options)
+                .await?;
+            let stream = stream
+                .map(move |element| {
+                    let _permit = &permit;
+                    element
+                })
+                .boxed();
+            Ok((res, stream))
+        })
+    }
+
+    // The interaction shares the limits.
+    fn create_interaction(&self, method_name: &'static CStr) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            transport: Arc::new(self.transport.create_interaction(method_name)?),
+            limiter: self.limiter.clone(),
+            phantom: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,53 @@
+use core::{ffi::CStr, time::Duration};
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallOutcome {
+    Success,
+    Timeout,
+    Failure,
+}
+
+impl CallOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Timeout => "timeout",
+            Self::Failure => "failure",
+        }
+    }
+}
+
+//
+#[derive(Debug, Clone)]
+pub struct CallMetrics {
+    pub service_name: &'static CStr,
+    pub fn_name: &'static CStr,
+    pub latency: Duration,
+    pub request_size: usize,
+    pub response_size: usize,
+    pub outcome: CallOutcome,
+    pub retry_count: usize,
+}
+
+//
+pub trait CallObserver: Send + Sync {
+    fn on_write_done(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        _request_size: usize,
+        _elapsed: Duration,
+    ) {
+    }
+
+    fn on_first_byte(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        _elapsed: Duration,
+    ) {
+    }
+
+    fn on_complete(&self, _metrics: &CallMetrics) {}
+}
@@ -0,0 +1,355 @@
+use core::{ffi::CStr, time::Duration};
+use std::{
+    io::{Cursor, Error as IoError, ErrorKind as IoErrorKind},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+};
+
+use async_sleep::Sleepble;
+use bytes::{Bytes, BytesMut};
+use fbthrift::{Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use fbthrift_transport_response_handler::ResponseHandlerV2;
+use futures_util::{
+    future::BoxFuture,
+    io::{AsyncRead, AsyncWrite},
+};
+
+use crate::{
+    idempotency::Idempotency,
+    transport::{AsyncTransport, AsyncTransportRpcOptions},
+};
+
+//
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+    idempotency: Idempotency,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            jitter: 0.5,
+            idempotency: Idempotency::new(),
+        }
+    }
+
+    pub fn set_max_retries(&mut self, n: usize) {
+        self.max_retries = n;
+    }
+
+    pub fn get_max_retries(&self) -> usize {
+        self.max_retries
+    }
+
+    pub fn set_initial_backoff(&mut self, dur: Duration) {
+        debug_assert!(dur <= self.max_backoff);
+        self.initial_backoff = dur;
+    }
+
+    pub fn get_initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    pub fn set_max_backoff(&mut self, dur: Duration) {
+        debug_assert!(dur >= self.initial_backoff);
+        self.max_backoff = dur;
+    }
+
+    pub fn get_max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    pub fn set_jitter(&mut self, jitter: f64) {
+        debug_assert!((0.0..=1.0).contains(&jitter));
+        self.jitter = jitter;
+    }
+
+    pub fn get_jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    // Only the idempotent functions are retried.
+    pub fn set_idempotency(&mut self, idempotency: Idempotency) {
+        self.idempotency = idempotency;
+    }
+
+    pub fn get_idempotency(&self) -> &Idempotency {
+        &self.idempotency
+    }
+
+    // retry starts at 1.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let exp = retry.saturating_sub(1).min(31) as u32;
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << exp)
+            .min(self.max_backoff);
+
+        backoff.mul_f64(1.0 - self.jitter * fastrand::f64())
+    }
+}
+
+pub fn is_retryable_error(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<IoError>() {
+        Some(err) => matches!(
+            err.kind(),
+            IoErrorKind::ConnectionReset
+                | IoErrorKind::ConnectionAborted
+                | IoErrorKind::ConnectionRefused
+                | IoErrorKind::BrokenPipe
+                | IoErrorKind::NotConnected
+                | IoErrorKind::TimedOut
+                | IoErrorKind::UnexpectedEof
+        ),
+        None => false,
+    }
+}
+
+//
+// Every call deposits `retry_ratio` tokens and every retry withdraws one,
+// `min_retries` tokens are always available. Deposits add up to at most those
+// of `RETRY_BUDGET_WINDOW` calls.
+#[derive(Debug)]
+pub struct RetryBudget {
+    balance: AtomicI64,
+    deposit_amount: i64,
+    max_balance: i64,
+}
+
+const RETRY_BUDGET_SCALE: i64 = 1000;
+const RETRY_BUDGET_WINDOW: i64 = 100;
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        Self::new(0.2, 10)
+    }
+}
+
+impl RetryBudget {
+    pub fn new(retry_ratio: f32, min_retries: u32) -> Self {
+        debug_assert!(retry_ratio >= 0.0);
+
+        let reserve = min_retries as i64 * RETRY_BUDGET_SCALE;
+        let deposit_amount = (retry_ratio * RETRY_BUDGET_SCALE as f32) as i64;
+
+        Self {
+            balance: AtomicI64::new(reserve),
+            deposit_amount,
+            max_balance: reserve + deposit_amount * RETRY_BUDGET_WINDOW,
+        }
+    }
+
+    pub fn deposit(&self) {
+        let _ = self
+            .balance
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |balance| {
+                Some((balance + self.deposit_amount).min(self.max_balance))
+            });
+    }
+
+    pub fn withdraw(&self) -> bool {
+        self.balance
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |balance| {
+                (balance >= RETRY_BUDGET_SCALE).then_some(balance - RETRY_BUDGET_SCALE)
+            })
+            .is_ok()
+    }
+}
+
+//
+pub struct RetryTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    transport: Arc<AsyncTransport<S, SLEEP, H>>,
+    policy: Arc<RetryPolicy>,
+    budget: Arc<RetryBudget>,
+}
+
+impl<S, SLEEP, H> RetryTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    pub fn new(transport: AsyncTransport<S, SLEEP, H>, policy: RetryPolicy) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            policy: Arc::new(policy),
+            budget: Arc::new(RetryBudget::default()),
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: Arc<RetryBudget>) {
+        self.budget = budget;
+    }
+
+    pub fn get_budget(&self) -> &Arc<RetryBudget> {
+        &self.budget
+    }
+
+    pub fn get_policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub fn get_ref(&self) -> &AsyncTransport<S, SLEEP, H> {
+        &self.transport
+    }
+}
+
+impl<S, SLEEP, H> Framing for RetryTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        Self::EncBuf::with_capacity(cap)
+    }
+}
+
+impl<S, SLEEP, H> Transport for RetryTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    SLEEP: Sleepble + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
+{
+    type RpcOptions = AsyncTransportRpcOptions;
+
+    fn call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let transport = self.transport.clone();
+        let policy = self.policy.clone();
+        let budget = self.budget.clone();
+
+        Box::pin(async move {
+            budget.deposit();
+            let idempotent = policy
+                .get_idempotency()
+                .is_idempotent(service_name, fn_name);
+
+            let mut rpc_options = rpc_options;
+            let mut retry = 0;
+            loop {
+                let mut ret = transport
+                    .call(service_name, fn_name, req.clone(), rpc_options.clone())
+                    .await;
+
+                loop {
+                    let err = match ret {
+                        Ok(cursor) => return Ok(cursor),
+                        Err(err) => err,
+                    };
+
+                    // Without a connector the failed connection would be reused, it may
+                    // still carry the response of the failed attempt.
+                    if !idempotent
+                        || transport.get_connector().is_none()
+                        || retry >= policy.get_max_retries()
+                        || !is_retryable_error(&err)
+                        || !budget.withdraw()
+                    {
+                        return Err(err);
+                    }
+                    retry += 1;
+
+                    async_sleep::sleep::<SLEEP>(policy.backoff(retry)).await;
+
+                    if let Err(err) = transport.reconnect().await {
+                        ret = Err(err.into());
+                        continue;
+                    }
+                    break;
+                }
+
+                rpc_options.retry_count = retry;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let mut p = RetryPolicy::new();
+        p.set_initial_backoff(Duration::from_millis(100));
+        p.set_max_backoff(Duration::from_millis(300));
+        p.set_jitter(0.0);
+        assert_eq!(p.backoff(1), Duration::from_millis(100));
+        assert_eq!(p.backoff(2), Duration::from_millis(200));
+        assert_eq!(p.backoff(3), Duration::from_millis(300));
+        assert_eq!(p.backoff(100), Duration::from_millis(300));
+
+        p.set_jitter(0.5);
+        for _ in 0..100 {
+            let backoff = p.backoff(1);
+            assert!(backoff > Duration::from_millis(50) && backoff <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn test_budget() {
+        let b = RetryBudget::new(0.5, 1);
+        assert!(b.withdraw());
+        assert!(!b.withdraw());
+
+        b.deposit();
+        assert!(!b.withdraw());
+        b.deposit();
+        assert!(b.withdraw());
+        assert!(!b.withdraw());
+    }
+
+    #[test]
+    fn test_budget_max_balance() {
+        let b = RetryBudget::new(0.5, 1);
+        for _ in 0..1000 {
+            b.deposit();
+        }
+
+        for _ in 0..51 {
+            assert!(b.withdraw());
+        }
+        assert!(!b.withdraw());
+    }
+
+    #[test]
+    fn test_is_retryable_error() {
+        assert!(is_retryable_error(
+            &IoError::new(IoErrorKind::TimedOut, "").into()
+        ));
+        assert!(is_retryable_error(
+            &IoError::new(IoErrorKind::ConnectionReset, "").into()
+        ));
+        assert!(!is_retryable_error(&IoError::other("").into()));
+        assert!(!is_retryable_error(&anyhow::anyhow!("foo")));
+    }
+}
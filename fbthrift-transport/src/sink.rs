@@ -0,0 +1,189 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use async_sleep::Sleepble;
+use bytes::Bytes;
+use fbthrift::FramingDecoded;
+use fbthrift_transport_response_handler::{Headers, ResponseHandlerV2};
+use futures_util::{
+    future,
+    io::{AsyncRead, AsyncWrite},
+    ready, Sink, SinkExt as _,
+};
+
+use crate::transport::{AsyncTransport, Call};
+
+//
+// Uploads the encoded items of a sink call after its initial response, `finish` then reads the
+// final response.
+//
+// One item is written at a time, `poll_ready` stays pending while it is, each write and flush
+// fails after the write timeout. Dropping it before `finish` completes marks the connection
+// desynchronized, as for `CallStream`.
+pub struct CallSink<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    call: Call<S, SLEEP, H>,
+    desynced: Arc<AtomicBool>,
+    pending_item: Option<(Bytes, usize)>,
+    complete_queued: bool,
+    failed: bool,
+    clean: bool,
+}
+
+impl<S, SLEEP, H> core::fmt::Debug for CallSink<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CallSink")
+            .field("pending_item", &self.pending_item.is_some())
+            .field("complete_queued", &self.complete_queued)
+            .field("failed", &self.failed)
+            .finish()
+    }
+}
+
+impl<S, SLEEP, H> CallSink<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    pub(crate) fn new(mut call: Call<S, SLEEP, H>, desynced: Arc<AtomicBool>) -> Self {
+        // A static response was not read from the connection, so no final response follows.
+        let clean = !call.is_reading();
+        if !clean {
+            call.set_stream_open(true);
+        }
+
+        Self {
+            call,
+            desynced,
+            pending_item: None,
+            complete_queued: false,
+            failed: false,
+            clean,
+        }
+    }
+
+    // The headers of the initial response.
+    pub fn get_response_headers(&self) -> Option<&Headers> {
+        self.call.get_response_headers()
+    }
+
+    // Writes the response handler's complete frame after the pushed items, then reads the final
+    // response.
+    pub async fn finish(mut self) -> anyhow::Result<FramingDecoded<AsyncTransport<S, SLEEP, H>>> {
+        if !self.call.is_reading() {
+            anyhow::bail!("Sink unavailable with a static response");
+        }
+
+        self.close().await?;
+
+        self.call.start_next_element();
+        let res = future::poll_fn(|cx| self.call.poll_next_element(cx)).await?;
+        self.clean = true;
+        self.call.set_stream_open(false);
+        self.call.release_buf();
+
+        Ok(Cursor::new(res))
+    }
+
+    fn poll_pending_item(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        if self.failed {
+            return Poll::Ready(Err(anyhow::Error::msg("Sink failed")));
+        }
+
+        if let Some((item, written)) = &mut self.pending_item {
+            if let Err(err) = ready!(self.call.poll_write_frame(cx, item, written)) {
+                self.failed = true;
+                return Poll::Ready(Err(err.into()));
+            }
+            self.pending_item = None;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush_frames(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        if let Err(err) = ready!(self.call.poll_flush_frames(cx)) {
+            self.failed = true;
+            return Poll::Ready(Err(err.into()));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S, SLEEP, H> Sink<Bytes> for CallSink<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    type Error = anyhow::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_pending_item(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        debug_assert!(this.pending_item.is_none());
+
+        if this.failed || this.complete_queued {
+            anyhow::bail!("Sink closed");
+        }
+        this.pending_item = Some((item, 0));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending_item(cx))?;
+        this.poll_flush_frames(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending_item(cx))?;
+
+        if !this.complete_queued {
+            this.complete_queued = true;
+            if let Some(frame) = this.call.make_sink_complete_bytes() {
+                this.pending_item = Some((frame.into(), 0));
+                ready!(this.poll_pending_item(cx))?;
+            }
+        }
+
+        this.poll_flush_frames(cx)
+    }
+}
+
+impl<S, SLEEP, H> Drop for CallSink<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn drop(&mut self) {
+        if !self.clean {
+            self.desynced.store(true, Ordering::Release);
+            self.call.set_stream_open(false);
+        }
+        self.call.release_buf();
+    }
+}
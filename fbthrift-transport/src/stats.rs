@@ -0,0 +1,214 @@
+use core::task::{Context, Poll, Waker};
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TransportStats {
+    pub calls_started: u64,
+    pub calls_succeeded: u64,
+    // Failed calls by error kind: read timeouts, connection errors, anything else such as
+    // response handler or buffer limit errors.
+    pub calls_timed_out: u64,
+    pub calls_failed_io: u64,
+    pub calls_failed_other: u64,
+    pub bytes_written: u64,
+    pub bytes_read: u64,
+    pub reconnects: u64,
+    // Every read and write timeout, streams and sinks included.
+    pub timeouts: u64,
+    // Running calls, and open streams or sinks.
+    pub in_flight: u64,
+}
+
+impl TransportStats {
+    pub fn calls_failed(&self) -> u64 {
+        self.calls_timed_out + self.calls_failed_io + self.calls_failed_other
+    }
+}
+
+//
+// Shared by a connection's transports and calls. Each counter is updated on its own, so a
+// snapshot taken while calls run may be off by the calls in progress.
+#[derive(Debug, Default)]
+pub(crate) struct TransportCounters {
+    calls_started: AtomicU64,
+    calls_succeeded: AtomicU64,
+    calls_timed_out: AtomicU64,
+    calls_failed_io: AtomicU64,
+    calls_failed_other: AtomicU64,
+    bytes_written: AtomicU64,
+    bytes_read: AtomicU64,
+    reconnects: AtomicU64,
+    timeouts: AtomicU64,
+    in_flight: AtomicU64,
+    // Woken once nothing is in flight.
+    idle_wakers: Mutex<Vec<Waker>>,
+}
+
+impl TransportCounters {
+    pub(crate) fn on_call_start(&self) {
+        self.calls_started.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_call_complete<T>(&self, ret: &Result<T, anyhow::Error>) {
+        self.exit_in_flight();
+
+        let counter = match ret {
+            Ok(_) => &self.calls_succeeded,
+            Err(err) => match err.downcast_ref::<IoError>().map(|err| err.kind()) {
+                Some(IoErrorKind::TimedOut) => &self.calls_timed_out,
+                Some(kind) if kind != IoErrorKind::Other => &self.calls_failed_io,
+                _ => &self.calls_failed_other,
+            },
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Dropped before completing.
+    pub(crate) fn on_call_cancel(&self) {
+        self.exit_in_flight();
+    }
+
+    // A stream or sink stays in flight until it ends or is dropped.
+    pub(crate) fn on_stream_open(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_stream_close(&self) {
+        self.exit_in_flight();
+    }
+
+    fn exit_in_flight(&self) {
+        if self.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            if let Ok(mut idle_wakers) = self.idle_wakers.lock() {
+                idle_wakers.drain(..).for_each(Waker::wake);
+            }
+        }
+    }
+
+    pub(crate) fn on_written(&self, n: usize) {
+        self.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_read(&self, n: usize) {
+        self.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_io_error(&self, err: &IoError) {
+        if err.kind() == IoErrorKind::TimedOut {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Checked again once the waker is registered, so a call ending in between still wakes it.
+    pub(crate) fn poll_idle(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.in_flight.load(Ordering::Acquire) == 0 {
+            return Poll::Ready(());
+        }
+
+        if let Ok(mut idle_wakers) = self.idle_wakers.lock() {
+            if !idle_wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                idle_wakers.push(cx.waker().clone());
+            }
+        }
+
+        if self.in_flight.load(Ordering::Acquire) == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> TransportStats {
+        TransportStats {
+            calls_started: self.calls_started.load(Ordering::Relaxed),
+            calls_succeeded: self.calls_succeeded.load(Ordering::Relaxed),
+            calls_timed_out: self.calls_timed_out.load(Ordering::Relaxed),
+            calls_failed_io: self.calls_failed_io.load(Ordering::Relaxed),
+            calls_failed_other: self.calls_failed_other.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{sync::Arc, task::Wake};
+
+    #[test]
+    fn test_on_call_complete() {
+        let counters = TransportCounters::default();
+
+        for _ in 0..5 {
+            counters.on_call_start();
+        }
+        counters.on_call_complete(&Ok(()));
+        counters.on_call_complete::<()>(&Err(IoError::from(IoErrorKind::TimedOut).into()));
+        counters.on_call_complete::<()>(&Err(IoError::from(IoErrorKind::BrokenPipe).into()));
+        counters.on_call_complete::<()>(&Err(IoError::other("Reach max buffer size").into()));
+
+        let stats = counters.snapshot();
+        assert_eq!(
+            stats,
+            TransportStats {
+                calls_started: 5,
+                calls_succeeded: 1,
+                calls_timed_out: 1,
+                calls_failed_io: 1,
+                calls_failed_other: 1,
+                in_flight: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.calls_failed(), 3);
+
+        counters.on_call_cancel();
+        assert_eq!(counters.snapshot().in_flight, 0);
+    }
+
+    #[test]
+    fn test_poll_idle() {
+        #[derive(Default)]
+        struct FooWake(AtomicU64);
+
+        impl Wake for FooWake {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let counters = TransportCounters::default();
+        let wake = Arc::new(FooWake::default());
+        let waker = Waker::from(wake.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(counters.poll_idle(&mut cx), Poll::Ready(()));
+
+        counters.on_call_start();
+        counters.on_stream_open();
+        assert_eq!(counters.poll_idle(&mut cx), Poll::Pending);
+        counters.on_call_complete(&Ok(()));
+        assert_eq!(counters.poll_idle(&mut cx), Poll::Pending);
+        assert_eq!(wake.0.load(Ordering::SeqCst), 0);
+
+        counters.on_stream_close();
+        assert_eq!(wake.0.load(Ordering::SeqCst), 1);
+        assert_eq!(counters.poll_idle(&mut cx), Poll::Ready(()));
+    }
+}
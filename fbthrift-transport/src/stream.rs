@@ -0,0 +1,223 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use async_sleep::Sleepble;
+use fbthrift::{ClientStreamElement, FramingDecoded};
+use fbthrift_transport_response_handler::{Headers, ResponseHandlerV2, StreamElementKind};
+use futures_util::{
+    future,
+    io::{AsyncRead, AsyncWrite},
+    ready, Stream, StreamExt as _,
+};
+
+use crate::transport::{AsyncTransport, Call};
+
+//
+// The elements following the initial response of a streaming call, ended by the response
+// handler's end-of-stream marker.
+//
+// Other calls on the connection fail while it is open. Dropping it before the end leaves unread
+// elements on the connection, which is then marked desynchronized, the next call reconnects first
+// or fails. `cancel` keeps the connection usable.
+pub struct CallStream<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    call: Call<S, SLEEP, H>,
+    desynced: Arc<AtomicBool>,
+    remaining_credits: u32,
+    pending_frame: Option<(Vec<u8>, usize)>,
+    element_started: bool,
+    finished: bool,
+    clean: bool,
+}
+
+impl<S, SLEEP, H> core::fmt::Debug for CallStream<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CallStream")
+            .field("remaining_credits", &self.remaining_credits)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+impl<S, SLEEP, H> CallStream<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    pub(crate) fn new(mut call: Call<S, SLEEP, H>, desynced: Arc<AtomicBool>) -> Self {
+        // A static response was not read from the connection, so nothing follows it.
+        let finished = !call.is_reading();
+        if !finished {
+            call.set_stream_open(true);
+        }
+
+        Self {
+            call,
+            desynced,
+            remaining_credits: 0,
+            pending_frame: None,
+            element_started: false,
+            finished,
+            clean: finished,
+        }
+    }
+
+    // The headers of the initial response.
+    pub fn get_response_headers(&self) -> Option<&Headers> {
+        self.call.get_response_headers()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Sends the response handler's cancel frame, then reads and drops the remaining elements up
+    // to the end-of-stream marker.
+    pub async fn cancel(mut self) -> anyhow::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        future::poll_fn(|cx| self.poll_pending_frame(cx)).await?;
+        if let Some(frame) = self.call.make_stream_cancel_bytes() {
+            self.pending_frame = Some((frame, 0));
+            future::poll_fn(|cx| self.poll_pending_frame(cx)).await?;
+        }
+
+        while let Some(element) = self.next().await {
+            element?;
+        }
+
+        Ok(())
+    }
+
+    fn poll_pending_frame(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        if let Some((frame, written)) = &mut self.pending_frame {
+            // Once written, `written` stays at the end, so only the flush is polled again.
+            let mut ret = ready!(self.call.poll_write_frame(cx, frame, written));
+            if ret.is_ok() {
+                ret = ready!(self.call.poll_flush_frames(cx));
+            }
+            if let Err(err) = ret {
+                self.finished = true;
+                return Poll::Ready(Err(err.into()));
+            }
+            self.pending_frame = None;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn finish(&mut self, clean: bool) {
+        self.finished = true;
+        self.clean = clean;
+        if clean {
+            self.call.set_stream_open(false);
+        }
+        self.call.release_buf();
+    }
+}
+
+impl<S, SLEEP, H> Stream for CallStream<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    type Item = anyhow::Result<ClientStreamElement<FramingDecoded<AsyncTransport<S, SLEEP, H>>>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.finished {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            if let Err(err) = ready!(this.poll_pending_frame(cx)) {
+                this.finish(false);
+                return Poll::Ready(Some(Err(err)));
+            }
+
+            let credits = this.call.get_configuration().get_stream_credits();
+            if credits > 0 && this.remaining_credits == 0 && !this.element_started {
+                this.remaining_credits = credits;
+                if let Some(frame) = this.call.make_stream_credits_bytes(credits) {
+                    this.pending_frame = Some((frame, 0));
+                    continue;
+                }
+            }
+
+            break;
+        }
+
+        if !this.element_started {
+            this.call.start_next_element();
+            this.element_started = true;
+        }
+
+        let ret = ready!(this.call.poll_next_element(cx));
+        this.element_started = false;
+
+        let bytes = match ret {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                this.finish(false);
+                return Poll::Ready(Some(Err(err)));
+            }
+        };
+
+        let element = match this.call.stream_element_kind(&bytes) {
+            Ok(StreamElementKind::Reply) => ClientStreamElement::Reply(Cursor::new(bytes)),
+            Ok(StreamElementKind::ApplicationEx) => {
+                ClientStreamElement::ApplicationEx(Cursor::new(bytes))
+            }
+            Ok(StreamElementKind::End) => {
+                this.finish(true);
+                return Poll::Ready(None);
+            }
+            Err(err) => {
+                this.finish(false);
+                return Poll::Ready(Some(Err(err.into())));
+            }
+        };
+
+        this.remaining_credits = this.remaining_credits.saturating_sub(1);
+
+        Poll::Ready(Some(Ok(element)))
+    }
+}
+
+impl<S, SLEEP, H> Drop for CallStream<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn drop(&mut self) {
+        // Once desynchronized, the connection is no longer busy, the next call reconnects first.
+        if !self.clean {
+            self.desynced.store(true, Ordering::Release);
+            self.call.set_stream_open(false);
+        }
+        self.call.release_buf();
+    }
+}
@@ -7,34 +7,142 @@ use core::{
 };
 use std::{
     io::{Cursor, Error as IoError, ErrorKind as IoErrorKind},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use async_sleep::{AsyncReadWithTimeoutExt as _, Sleepble};
+use async_lock::{Mutex as AsyncMutex, MutexGuardArc};
+use async_sleep::{
+    rw::{async_read_poll, async_write_poll},
+    Sleepble, SleepbleWaitBoxFuture,
+};
 use bytes::{Bytes, BytesMut};
-use fbthrift::{Framing, FramingDecoded, FramingEncodedFinal, Transport};
-use fbthrift_transport_response_handler::ResponseHandler;
+use fbthrift::{ClientStreamElement, Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use fbthrift_transport_response_handler::{
+    CallContext, Headers, ResponseHandlerV2, ResponseParseOutcome, StreamElementKind,
+};
 use futures_util::{
-    future::BoxFuture,
-    io::{AsyncRead, AsyncWrite, AsyncWriteExt as _},
+    future::{self, BoxFuture},
+    io::{AsyncRead, AsyncWrite},
     ready,
+    stream::BoxStream,
+    StreamExt as _,
 };
 
-use crate::configuration::AsyncTransportConfiguration;
+use crate::{
+    configuration::AsyncTransportConfiguration,
+    connector::Connector,
+    interceptor::Interceptor,
+    observer::{CallMetrics, CallOutcome},
+    sink::CallSink,
+    stats::{TransportCounters, TransportStats},
+    stream::CallStream,
+    wire_dump::WireDumpKind,
+};
 
 //
 #[derive(Debug, Clone, Default)]
-pub struct AsyncTransportRpcOptions {}
+pub struct AsyncTransportRpcOptions {
+    pub retry_count: usize,
+    // The request is written and flushed, the call resolves with an empty response without
+    // reading anything, for Thrift `oneway` functions.
+    pub oneway: bool,
+    // Request-scoped metadata, e.g. client id or tenant. The transport does not encode them, the
+    // response handler's `make_request_bytes_with_headers` must, e.g. as THeader info headers.
+    // With a handler returning None, the request is sent without them.
+    pub headers: Headers,
+}
+
+type Reconnect = BoxFuture<'static, Result<(), IoError>>;
+
+pub type OpenedStream<S, SLEEP, H> = (
+    FramingDecoded<AsyncTransport<S, SLEEP, H>>,
+    CallStream<S, SLEEP, H>,
+);
+pub type OpenedSink<S, SLEEP, H> = (
+    FramingDecoded<AsyncTransport<S, SLEEP, H>>,
+    CallSink<S, SLEEP, H>,
+);
+
+//
+// Terminated when the interaction's transport is dropped, by queueing the response handler's
+// terminate frame for the next call on the connection. Lost once the connection is replaced, as
+// the server has no state for it on the new one.
+struct Interaction {
+    id: i64,
+    method_name: &'static CStr,
+    terminate_bytes: Option<Vec<u8>>,
+    pending_frames: Arc<Mutex<Vec<u8>>>,
+    // The connection generation it was created on.
+    generation: u64,
+    connection_generation: Arc<AtomicU64>,
+}
+
+impl Interaction {
+    fn is_lost(&self) -> bool {
+        self.connection_generation.load(Ordering::Acquire) != self.generation
+    }
+}
+
+impl Drop for Interaction {
+    fn drop(&mut self) {
+        if self.is_lost() {
+            return;
+        }
+
+        if let Some(terminate_bytes) = self.terminate_bytes.take() {
+            if let Ok(mut pending_frames) = self.pending_frames.lock() {
+                pending_frames.extend_from_slice(&terminate_bytes);
+            }
+        }
+    }
+}
+
+fn interaction_lost_error() -> IoError {
+    IoError::other("Interaction lost, its connection was replaced")
+}
+
+fn closed_error() -> IoError {
+    IoError::other("Transport closed")
+}
+
+fn clear_pending_frames(pending_frames: &Mutex<Vec<u8>>) {
+    // Queued for the replaced connection, meaningless on the new one.
+    if let Ok(mut pending_frames) = pending_frames.lock() {
+        pending_frames.clear();
+    }
+}
 
 //
+// One connection, running one call at a time. The calls made meanwhile, those of its
+// interactions included, wait for it in order. An open stream or sink keeps the connection until
+// it ends, the calls made meanwhile fail. Concurrent calls go faster over several connections,
+// such as with `BalancedTransport`.
 pub struct AsyncTransport<S, SLEEP, H>
 where
     S: AsyncRead + AsyncWrite + Unpin,
     SLEEP: Sleepble,
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     stream: Arc<Mutex<S>>,
     configuration: AsyncTransportConfiguration<H>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    connector: Option<Arc<dyn Connector<S>>>,
+    seq_id: AtomicU32,
+    desynced: Arc<AtomicBool>,
+    busy: Arc<AtomicBool>,
+    // Held by the running call.
+    slot: Arc<AsyncMutex<()>>,
+    // Bumped every time the connection is replaced.
+    generation: Arc<AtomicU64>,
+    closed: Arc<AtomicBool>,
+    pending_frames: Arc<Mutex<Vec<u8>>>,
+    interaction_ids: Arc<AtomicI64>,
+    interaction: Option<Interaction>,
+    counters: Arc<TransportCounters>,
     phantom: PhantomData<SLEEP>,
 }
 
@@ -42,21 +150,228 @@ impl<S, SLEEP, H> AsyncTransport<S, SLEEP, H>
 where
     S: AsyncRead + AsyncWrite + Unpin,
     SLEEP: Sleepble,
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     pub fn new(stream: S, configuration: AsyncTransportConfiguration<H>) -> Self {
         Self {
             stream: Arc::new(Mutex::new(stream)),
             configuration,
+            interceptors: vec![],
+            connector: None,
+            seq_id: AtomicU32::new(0),
+            desynced: Arc::new(AtomicBool::new(false)),
+            busy: Arc::new(AtomicBool::new(false)),
+            slot: Arc::new(AsyncMutex::new(())),
+            generation: Arc::new(AtomicU64::new(0)),
+            closed: Arc::new(AtomicBool::new(false)),
+            pending_frames: Arc::new(Mutex::new(vec![])),
+            interaction_ids: Arc::new(AtomicI64::new(0)),
+            interaction: None,
+            counters: Arc::new(TransportCounters::default()),
             phantom: PhantomData,
         }
     }
+
+    pub async fn with_connector(
+        connector: Arc<dyn Connector<S>>,
+        configuration: AsyncTransportConfiguration<H>,
+    ) -> Result<Self, IoError> {
+        let stream = connector.connect().await?;
+
+        let mut this = Self::new(stream, configuration);
+        this.connector = Some(connector);
+        Ok(this)
+    }
+
+    pub fn set_connector(&mut self, connector: Arc<dyn Connector<S>>) {
+        self.connector = Some(connector);
+    }
+
+    pub fn get_connector(&self) -> Option<&Arc<dyn Connector<S>>> {
+        self.connector.as_ref()
+    }
+
+    pub async fn reconnect(&self) -> Result<(), IoError> {
+        if self.is_closed() {
+            return Err(closed_error());
+        }
+
+        let connector = self
+            .connector
+            .as_ref()
+            .ok_or_else(|| IoError::other("Missing connector"))?;
+        let stream = connector.connect().await?;
+
+        *self
+            .stream
+            .lock()
+            .map_err(|err| IoError::other(err.to_string()))? = stream;
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.desynced.store(false, Ordering::Release);
+        clear_pending_frames(&self.pending_frames);
+        self.counters.on_reconnect();
+
+        Ok(())
+    }
+
+    // Stops new calls, waits up to `timeout` for the in-flight ones, open streams and sinks
+    // included, then writes the queued frames and shuts the write half down. Later calls, and those
+    // of its interactions, fail with "Transport closed". Fails with `TimedOut` when calls were
+    // still running, after the shutdown.
+    pub async fn close(&self, timeout: Duration) -> Result<(), IoError> {
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        let idle = future::poll_fn(|cx| self.counters.poll_idle(cx));
+        let timed_out = matches!(
+            future::select(idle, SLEEP::sleep(timeout).wait()).await,
+            future::Either::Right(_)
+        );
+
+        let frames = self
+            .pending_frames
+            .lock()
+            .map(|mut pending_frames| core::mem::take(&mut *pending_frames))
+            .unwrap_or_default();
+        let mut written = 0;
+        let mut write_timeout_future = SLEEP::sleep(self.configuration.get_write_timeout()).wait();
+        future::poll_fn(|cx| {
+            let stream = &mut match self.stream.lock() {
+                Ok(stream) => stream,
+                Err(err) => return Poll::Ready(Err(IoError::other(err.to_string()))),
+            };
+
+            while written < frames.len() {
+                let n = ready!(async_write_poll(
+                    &mut **stream,
+                    &frames[written..],
+                    &mut write_timeout_future,
+                    cx,
+                ))?;
+                if n == 0 {
+                    return Poll::Ready(Err(IoErrorKind::WriteZero.into()));
+                }
+                written += n;
+            }
+
+            match Pin::new(&mut **stream).poll_close(cx) {
+                Poll::Ready(ret) => Poll::Ready(ret),
+                Poll::Pending => match write_timeout_future.as_mut().poll(cx) {
+                    Poll::Ready(_) => {
+                        Poll::Ready(Err(IoError::new(IoErrorKind::TimedOut, "write timeout")))
+                    }
+                    Poll::Pending => Poll::Pending,
+                },
+            }
+        })
+        .await?;
+
+        if timed_out {
+            return Err(IoError::new(
+                IoErrorKind::TimedOut,
+                "In-flight calls still running at close",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    // Counters of the connection, shared with the interactions created on it.
+    pub fn stats(&self) -> TransportStats {
+        self.counters.snapshot()
+    }
+
+    // Set when a streaming call was dropped before its end.
+    pub fn is_desynced(&self) -> bool {
+        self.desynced.load(Ordering::Acquire)
+    }
+
+    // For callers that dropped a call before its end, the next call reconnects first.
+    pub(crate) fn desync(&self) {
+        self.desynced.store(true, Ordering::Release);
+    }
+
+    pub fn get_interaction_id(&self) -> Option<i64> {
+        self.interaction.as_ref().map(|interaction| interaction.id)
+    }
+
+    pub fn get_interaction_method_name(&self) -> Option<&'static CStr> {
+        self.interaction
+            .as_ref()
+            .map(|interaction| interaction.method_name)
+    }
+
+    pub fn add_interceptor(&mut self, interceptor: Arc<dyn Interceptor>) {
+        self.interceptors.push(interceptor);
+    }
+
+    pub fn get_interceptors(&self) -> &[Arc<dyn Interceptor>] {
+        &self.interceptors
+    }
+
+    pub fn call_oneway(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+    ) -> BoxFuture<'static, anyhow::Result<()>>
+    where
+        Self: Transport<RpcOptions = AsyncTransportRpcOptions>,
+    {
+        let rpc_options = AsyncTransportRpcOptions {
+            oneway: true,
+            ..Default::default()
+        };
+        let call = self.call(service_name, fn_name, req, rpc_options);
+
+        Box::pin(async move { call.await.map(|_| ()) })
+    }
+
+    fn next_seq_id(&self) -> u32 {
+        self.seq_id.fetch_add(1, Ordering::Relaxed).wrapping_add(1)
+    }
+
+    fn new_call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: AsyncTransportRpcOptions,
+    ) -> Call<S, SLEEP, H> {
+        let call = Call::new(
+            self.stream.clone(),
+            service_name,
+            fn_name,
+            req,
+            rpc_options,
+            self.configuration.clone(),
+        )
+        .with_seq_id(self.next_seq_id())
+        .with_desynced(self.desynced.clone())
+        .with_busy(self.busy.clone())
+        .with_slot(self.slot.clone())
+        .with_closed(self.closed.clone())
+        .with_pending_frames(self.pending_frames.clone())
+        .with_counters(self.counters.clone());
+
+        match &self.interaction {
+            Some(interaction) => call
+                .with_interaction_id(interaction.id)
+                .with_generation(self.generation.clone(), interaction.generation),
+            None => call,
+        }
+    }
 }
 
 #[cfg(feature = "impl_tokio")]
 impl<H> AsyncTransport<crate::impl_tokio::TokioTcpStream, crate::impl_tokio::TokioSleep, H>
 where
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     pub async fn with_tokio_tcp_connect<A: tokio::net::ToSocketAddrs>(
         addr: A,
@@ -64,11 +379,7 @@ where
     ) -> Result<Self, IoError> {
         let stream = crate::impl_tokio::tcp_connect(addr).await?;
 
-        Ok(Self {
-            stream: Arc::new(Mutex::new(stream)),
-            configuration,
-            phantom: PhantomData,
-        })
+        Ok(Self::new(stream, configuration))
     }
 }
 
@@ -76,7 +387,7 @@ where
 impl<H>
     AsyncTransport<crate::impl_async_io::AsyncIoTcpStream, crate::impl_async_io::AsyncIoSleep, H>
 where
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     pub async fn with_async_io_tcp_connect<A: Into<std::net::SocketAddr>>(
         addr: A,
@@ -84,11 +395,7 @@ where
     ) -> Result<Self, IoError> {
         let stream = crate::impl_async_io::tcp_connect(addr).await?;
 
-        Ok(Self {
-            stream: Arc::new(Mutex::new(stream)),
-            configuration,
-            phantom: PhantomData,
-        })
+        Ok(Self::new(stream, configuration))
     }
 }
 
@@ -97,7 +404,7 @@ impl<S, SLEEP, H> Framing for AsyncTransport<S, SLEEP, H>
 where
     S: AsyncRead + AsyncWrite + Unpin,
     SLEEP: Sleepble,
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     type EncBuf = BytesMut;
     type DecBuf = Cursor<Bytes>;
@@ -111,7 +418,7 @@ impl<S, SLEEP, H> Transport for AsyncTransport<S, SLEEP, H>
 where
     S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
     SLEEP: Sleepble + Send + Sync + 'static,
-    H: ResponseHandler + Unpin + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
 {
     type RpcOptions = AsyncTransportRpcOptions;
 
@@ -122,14 +429,251 @@ where
         req: FramingEncodedFinal<Self>,
         rpc_options: Self::RpcOptions,
     ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
-        Pin::from(Box::new(Call::<S, SLEEP, H>::new(
-            self.stream.clone(),
-            service_name,
-            fn_name,
-            req,
-            rpc_options,
-            self.configuration.clone(),
-        )))
+        self.call_inner(service_name, fn_name, req, rpc_options)
+    }
+
+    fn call_stream(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<
+        'static,
+        anyhow::Result<(
+            FramingDecoded<Self>,
+            BoxStream<'static, anyhow::Result<ClientStreamElement<FramingDecoded<Self>>>>,
+        )>,
+    > {
+        let open = self.open_stream(service_name, fn_name, req, rpc_options);
+
+        Box::pin(async move {
+            let (res, stream) = open.await?;
+            Ok((res, stream.boxed()))
+        })
+    }
+
+    // The interaction shares the connection, without the connector, as reconnecting would lose
+    // its state on the server.
+    fn create_interaction(&self, method_name: &'static CStr) -> Result<Self, anyhow::Error> {
+        if self.interaction.is_some() {
+            anyhow::bail!("Nested interactions are not supported");
+        }
+
+        let id = self.interaction_ids.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut configuration = self.configuration.clone();
+        let terminate_bytes = configuration
+            .response_handler
+            .make_interaction_terminate_bytes(id);
+
+        Ok(Self {
+            stream: self.stream.clone(),
+            configuration,
+            interceptors: self.interceptors.clone(),
+            connector: None,
+            seq_id: AtomicU32::new(0),
+            desynced: self.desynced.clone(),
+            busy: self.busy.clone(),
+            slot: self.slot.clone(),
+            generation: self.generation.clone(),
+            closed: self.closed.clone(),
+            pending_frames: self.pending_frames.clone(),
+            interaction_ids: self.interaction_ids.clone(),
+            interaction: Some(Interaction {
+                id,
+                method_name,
+                terminate_bytes,
+                pending_frames: self.pending_frames.clone(),
+                generation: self.generation.load(Ordering::Acquire),
+                connection_generation: self.generation.clone(),
+            }),
+            counters: self.counters.clone(),
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<S, SLEEP, H> AsyncTransport<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    SLEEP: Sleepble + Send + Sync + 'static,
+    H: ResponseHandlerV2 + Unpin + Send + Sync + 'static,
+{
+    // Resolves with the initial response and the stream of the following elements.
+    pub fn open_stream(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: AsyncTransportRpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<OpenedStream<S, SLEEP, H>>> {
+        self.open_call(service_name, fn_name, req, rpc_options, CallStream::new)
+    }
+
+    // Resolves with the initial response and the sink to push the items into.
+    pub fn open_sink(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: AsyncTransportRpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<OpenedSink<S, SLEEP, H>>> {
+        self.open_call(service_name, fn_name, req, rpc_options, CallSink::new)
+    }
+
+    fn open_call<T>(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: AsyncTransportRpcOptions,
+        wrap: fn(Call<S, SLEEP, H>, Arc<AtomicBool>) -> T,
+    ) -> BoxFuture<'static, anyhow::Result<(FramingDecoded<Self>, T)>>
+    where
+        T: Send + 'static,
+    {
+        let (req, reconnect) = match self.prepare_call(service_name, fn_name, req) {
+            Ok(prepared) => prepared,
+            Err(err) => return Box::pin(future::ready(Err(err))),
+        };
+
+        let interceptors = self.interceptors.clone();
+        let desynced = self.desynced.clone();
+        let mut call = self
+            .new_call(service_name, fn_name, req.clone(), rpc_options)
+            .keep_remaining();
+
+        Box::pin(async move {
+            if let Some(reconnect) = reconnect {
+                reconnect.await?;
+            }
+
+            let mut ret = future::poll_fn(|cx| call.poll_response(cx))
+                .await
+                .map(Cursor::into_inner);
+            for interceptor in interceptors.iter().rev() {
+                ret = interceptor.after_call(service_name, fn_name, &req, ret);
+            }
+
+            match ret {
+                Ok(res) => Ok((Cursor::new(res), wrap(call, desynced))),
+                Err(err) => {
+                    call.release_buf();
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    // Resolves with the response and the headers the response handler parsed from it, empty for
+    // a static response.
+    pub fn call_with_response_headers(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: AsyncTransportRpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<(FramingDecoded<Self>, Headers)>> {
+        let (req, reconnect) = match self.prepare_call(service_name, fn_name, req) {
+            Ok(prepared) => prepared,
+            Err(err) => return Box::pin(future::ready(Err(err))),
+        };
+
+        let interceptors = self.interceptors.clone();
+        let mut call = self.new_call(service_name, fn_name, req.clone(), rpc_options);
+
+        Box::pin(async move {
+            if let Some(reconnect) = reconnect {
+                reconnect.await?;
+            }
+
+            let mut ret = (&mut call).await.map(Cursor::into_inner);
+            for interceptor in interceptors.iter().rev() {
+                ret = interceptor.after_call(service_name, fn_name, &req, ret);
+            }
+
+            let headers = call.get_response_headers().cloned().unwrap_or_default();
+            ret.map(|res| (Cursor::new(res), headers))
+        })
+    }
+
+    fn call_inner(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        rpc_options: AsyncTransportRpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        let (req, reconnect) = match self.prepare_call(service_name, fn_name, req) {
+            Ok(prepared) => prepared,
+            Err(err) => return Box::pin(future::ready(Err(err))),
+        };
+
+        if self.interceptors.is_empty() && reconnect.is_none() {
+            return Pin::from(Box::new(self.new_call(
+                service_name,
+                fn_name,
+                req,
+                rpc_options,
+            )));
+        }
+
+        let interceptors = self.interceptors.clone();
+        let call = self.new_call(service_name, fn_name, req.clone(), rpc_options);
+
+        Box::pin(async move {
+            if let Some(reconnect) = reconnect {
+                reconnect.await?;
+            }
+
+            let mut ret = call.await.map(Cursor::into_inner);
+            for interceptor in interceptors.iter().rev() {
+                ret = interceptor.after_call(service_name, fn_name, &req, ret);
+            }
+            ret.map(Cursor::new)
+        })
+    }
+
+    // The request after the interceptors, and the reconnect to await before writing it when the
+    // connection is desynchronized.
+    fn prepare_call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+    ) -> anyhow::Result<(FramingEncodedFinal<Self>, Option<Reconnect>)> {
+        let reconnect = self.reconnect_if_desynced();
+
+        let mut req = req;
+        for interceptor in self.interceptors.iter() {
+            req = interceptor.before_call(service_name, fn_name, req)?;
+        }
+
+        Ok((req, reconnect))
+    }
+
+    fn reconnect_if_desynced(&self) -> Option<Reconnect> {
+        if !self.is_desynced() || self.is_closed() {
+            return None;
+        }
+
+        let connector = self.connector.clone()?;
+        let stream = self.stream.clone();
+        let desynced = self.desynced.clone();
+        let generation = self.generation.clone();
+        let pending_frames = self.pending_frames.clone();
+        let counters = self.counters.clone();
+        Some(Box::pin(async move {
+            let new_stream = connector.connect().await?;
+            *stream
+                .lock()
+                .map_err(|err| IoError::other(err.to_string()))? = new_stream;
+            generation.fetch_add(1, Ordering::AcqRel);
+            desynced.store(false, Ordering::Release);
+            clear_pending_frames(&pending_frames);
+            counters.on_reconnect();
+            Ok(())
+        }))
     }
 }
 
@@ -138,32 +682,60 @@ where
 enum CallState {
     Pending,
     Writed,
+    Reading,
 }
 
 pub struct Call<S, SLEEP, H>
 where
     S: AsyncRead + AsyncWrite + Unpin,
     SLEEP: Sleepble,
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     stream: Arc<Mutex<S>>,
     service_name: &'static CStr,
     fn_name: &'static CStr,
     req: FramingEncodedFinal<AsyncTransport<S, SLEEP, H>>,
-    #[allow(dead_code)]
     rpc_options: AsyncTransportRpcOptions,
     configuration: AsyncTransportConfiguration<H>,
     //
+    seq_id: u32,
     state: CallState,
-    buf_storage: Vec<u8>,
+    req_written: usize,
+    buf_storage: BytesMut,
+    // Set while a read is pending, buf_storage then keeps its zeroed read region past this len.
+    filled_len: Option<usize>,
+    parse_pending: bool,
+    parse_at_len: usize,
+    parsed_len: usize,
     parsed_response_bytes_count: u8,
+    response_headers: Option<Headers>,
+    interaction_id: i64,
+    keep_remaining: bool,
+    desynced: Option<Arc<AtomicBool>>,
+    busy: Option<Arc<AtomicBool>>,
+    slot: Option<Arc<AsyncMutex<()>>>,
+    slot_lock: Option<BoxFuture<'static, MutexGuardArc<()>>>,
+    slot_guard: Option<MutexGuardArc<()>>,
+    generation: Option<(Arc<AtomicU64>, u64)>,
+    stream_open: bool,
+    closed: Option<Arc<AtomicBool>>,
+    pending_frames: Option<Arc<Mutex<Vec<u8>>>>,
+    prelude: Option<(Vec<u8>, usize)>,
+    counters: Option<Arc<TransportCounters>>,
+    in_flight: bool,
+    read_timeout_future: Option<SleepbleWaitBoxFuture>,
+    write_timeout_future: Option<SleepbleWaitBoxFuture>,
+    started_at: Instant,
+    first_byte_observed: bool,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl<S, SLEEP, H> Call<S, SLEEP, H>
 where
     S: AsyncRead + AsyncWrite + Unpin,
     SLEEP: Sleepble,
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     pub fn new(
         stream: Arc<Mutex<S>>,
@@ -171,9 +743,16 @@ where
         fn_name: &'static CStr,
         req: FramingEncodedFinal<AsyncTransport<S, SLEEP, H>>,
         rpc_options: AsyncTransportRpcOptions,
-        configuration: AsyncTransportConfiguration<H>,
+        mut configuration: AsyncTransportConfiguration<H>,
     ) -> Self {
-        let max_buf_size = configuration.get_max_buf_size();
+        configuration.response_handler.reset();
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "fbthrift_transport_call",
+            service_name = %service_name.to_string_lossy(),
+            fn_name = %fn_name.to_string_lossy(),
+        );
 
         Self {
             stream,
@@ -182,28 +761,540 @@ where
             req,
             rpc_options,
             configuration,
+            seq_id: 0,
             state: CallState::Pending,
-            buf_storage: Vec::with_capacity(max_buf_size),
+            req_written: 0,
+            buf_storage: BytesMut::new(),
+            filled_len: None,
+            parse_pending: false,
+            parse_at_len: 0,
+            parsed_len: 0,
             parsed_response_bytes_count: 0,
+            response_headers: None,
+            interaction_id: 0,
+            keep_remaining: false,
+            desynced: None,
+            busy: None,
+            slot: None,
+            slot_lock: None,
+            slot_guard: None,
+            generation: None,
+            stream_open: false,
+            closed: None,
+            pending_frames: None,
+            prelude: None,
+            counters: None,
+            in_flight: false,
+            read_timeout_future: None,
+            write_timeout_future: None,
+            started_at: Instant::now(),
+            first_byte_observed: false,
+            #[cfg(feature = "tracing")]
+            span,
         }
     }
+
+    pub fn with_seq_id(mut self, seq_id: u32) -> Self {
+        self.seq_id = seq_id;
+        self
+    }
+
+    pub(crate) fn with_interaction_id(mut self, interaction_id: i64) -> Self {
+        self.interaction_id = interaction_id;
+        self
+    }
+
+    // Frames queued on the connection, written before the request.
+    pub(crate) fn with_pending_frames(mut self, pending_frames: Arc<Mutex<Vec<u8>>>) -> Self {
+        self.pending_frames = Some(pending_frames);
+        self
+    }
+
+    // Fails the call before writing anything while the flag is set.
+    pub(crate) fn with_desynced(mut self, desynced: Arc<AtomicBool>) -> Self {
+        self.desynced = Some(desynced);
+        self
+    }
+
+    // Fails the call before writing anything while a stream or sink is open on the connection.
+    pub(crate) fn with_busy(mut self, busy: Arc<AtomicBool>) -> Self {
+        self.busy = Some(busy);
+        self
+    }
+
+    // Waits for the slot before writing anything, and holds it until the response is read, or until
+    // the stream or sink is open.
+    pub(crate) fn with_slot(mut self, slot: Arc<AsyncMutex<()>>) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+
+    // Fails the call before writing anything once the connection is no longer `generation`.
+    pub(crate) fn with_generation(mut self, current: Arc<AtomicU64>, generation: u64) -> Self {
+        self.generation = Some((current, generation));
+        self
+    }
+
+    pub(crate) fn with_counters(mut self, counters: Arc<TransportCounters>) -> Self {
+        self.counters = Some(counters);
+        self
+    }
+
+    // Fails the call before writing anything once the transport is closed.
+    pub(crate) fn with_closed(mut self, closed: Arc<AtomicBool>) -> Self {
+        self.closed = Some(closed);
+        self
+    }
+
+    // Streaming, the bytes following a response are kept for the next element.
+    pub(crate) fn keep_remaining(mut self) -> Self {
+        self.keep_remaining = true;
+        self
+    }
+
+    // Parsed from the initial response, None until it is read.
+    pub fn get_response_headers(&self) -> Option<&Headers> {
+        self.response_headers.as_ref()
+    }
+
+    fn observe_complete(&self, ret: &<Self as Future>::Output) {
+        let observer = match self.configuration.get_call_observer() {
+            Some(observer) => observer,
+            None => return,
+        };
+
+        let (outcome, response_size) = match ret {
+            Ok(cursor) => (CallOutcome::Success, cursor.get_ref().len()),
+            Err(err) => match err.downcast_ref::<IoError>() {
+                Some(err) if err.kind() == IoErrorKind::TimedOut => (CallOutcome::Timeout, 0),
+                _ => (CallOutcome::Failure, 0),
+            },
+        };
+
+        observer.on_complete(&CallMetrics {
+            service_name: self.service_name,
+            fn_name: self.fn_name,
+            latency: self.started_at.elapsed(),
+            request_size: self.req.len(),
+            response_size,
+            outcome,
+            retry_count: self.rpc_options.retry_count,
+        });
+    }
 }
 
 impl<S, SLEEP, H> Future for Call<S, SLEEP, H>
 where
     S: AsyncRead + AsyncWrite + Unpin,
     SLEEP: Sleepble,
-    H: ResponseHandler + Unpin,
+    H: ResponseHandlerV2 + Unpin,
 {
     type Output = Result<FramingDecoded<AsyncTransport<S, SLEEP, H>>, anyhow::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         let this = self.get_mut();
-        let stream = &mut match this.stream.lock() {
+
+        let ret = ready!(this.poll_response(cx));
+        this.release_buf();
+
+        Poll::Ready(ret)
+    }
+}
+
+impl<S, SLEEP, H> Call<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    pub(crate) fn poll_response(&mut self, cx: &mut Context) -> Poll<<Self as Future>::Output> {
+        #[cfg(feature = "tracing")]
+        let _span = self.enter_span();
+
+        let this = self;
+
+        if let Some(counters) = &this.counters {
+            if !this.in_flight {
+                this.in_flight = true;
+                counters.on_call_start();
+            }
+        }
+
+        let ret = ready!(this.poll_call(cx));
+        this.observe_complete(&ret);
+
+        // A stream or sink gives the slot up once it is marked open.
+        if !(this.keep_remaining && ret.is_ok() && this.is_reading()) {
+            this.slot_guard = None;
+        }
+
+        if let Some(counters) = &this.counters {
+            this.in_flight = false;
+            counters.on_call_complete(&ret);
+        }
+
+        #[cfg(feature = "tracing")]
+        match &ret {
+            Ok(cursor) => {
+                tracing::debug!(response_size = cursor.get_ref().len(), "call completed")
+            }
+            Err(err) => match err.downcast_ref::<IoError>() {
+                Some(err) if err.kind() == IoErrorKind::TimedOut => {
+                    tracing::warn!(error = %err, "call timed out")
+                }
+                _ => tracing::warn!(error = %err, "call failed"),
+            },
+        }
+
+        Poll::Ready(ret)
+    }
+
+    // Entered by every poll path, so that the events of streams and sinks belong to the call too.
+    #[cfg(feature = "tracing")]
+    fn enter_span(&self) -> tracing::span::EnteredSpan {
+        self.span.clone().entered()
+    }
+
+    pub(crate) fn release_buf(&mut self) {
+        if let Some(pool) = self.configuration.get_buffer_pool() {
+            if self.buf_storage.capacity() > 0 {
+                pool.put(core::mem::take(&mut self.buf_storage));
+            }
+        }
+    }
+
+    // False when the response was static, nothing was read from the connection.
+    // Set by the stream or sink of the call until it ends, other calls on the connection fail
+    // meanwhile and `close` waits for it.
+    pub(crate) fn set_stream_open(&mut self, open: bool) {
+        if self.stream_open == open {
+            return;
+        }
+        self.stream_open = open;
+
+        if let Some(busy) = &self.busy {
+            busy.store(open, Ordering::Release);
+        }
+        self.slot_guard = None;
+        if let Some(counters) = &self.counters {
+            if open {
+                counters.on_stream_open();
+            } else {
+                counters.on_stream_close();
+            }
+        }
+    }
+
+    pub(crate) fn is_reading(&self) -> bool {
+        self.state == CallState::Reading
+    }
+
+    pub(crate) fn start_next_element(&mut self) {
+        debug_assert!(self.is_reading());
+
+        self.configuration.response_handler.reset_element();
+        self.parse_pending = !self.buf_storage.is_empty();
+        self.parse_at_len = 0;
+        self.parsed_len = 0;
+        self.parsed_response_bytes_count = 0;
+        self.read_timeout_future = None;
+    }
+
+    pub(crate) fn poll_next_element(&mut self, cx: &mut Context) -> Poll<anyhow::Result<Bytes>> {
+        debug_assert!(self.is_reading());
+
+        #[cfg(feature = "tracing")]
+        let _span = self.enter_span();
+
+        self.poll_call(cx).map_ok(Cursor::into_inner)
+    }
+
+    pub(crate) fn stream_element_kind(
+        &mut self,
+        element_bytes: &[u8],
+    ) -> Result<StreamElementKind, IoError> {
+        let ctx = CallContext {
+            service_name: self.service_name.to_bytes(),
+            fn_name: self.fn_name.to_bytes(),
+            request_bytes: &self.req[..],
+            seq_id: self.seq_id,
+            interaction_id: self.interaction_id,
+        };
+        self.configuration
+            .response_handler
+            .stream_element_kind(&ctx, element_bytes)
+    }
+
+    pub(crate) fn make_stream_credits_bytes(&mut self, credits: u32) -> Option<Vec<u8>> {
+        let ctx = CallContext {
+            service_name: self.service_name.to_bytes(),
+            fn_name: self.fn_name.to_bytes(),
+            request_bytes: &self.req[..],
+            seq_id: self.seq_id,
+            interaction_id: self.interaction_id,
+        };
+        self.configuration
+            .response_handler
+            .make_stream_credits_bytes(&ctx, credits)
+    }
+
+    pub(crate) fn make_stream_cancel_bytes(&mut self) -> Option<Vec<u8>> {
+        let ctx = CallContext {
+            service_name: self.service_name.to_bytes(),
+            fn_name: self.fn_name.to_bytes(),
+            request_bytes: &self.req[..],
+            seq_id: self.seq_id,
+            interaction_id: self.interaction_id,
+        };
+        self.configuration
+            .response_handler
+            .make_stream_cancel_bytes(&ctx)
+    }
+
+    pub(crate) fn make_sink_complete_bytes(&mut self) -> Option<Vec<u8>> {
+        let ctx = CallContext {
+            service_name: self.service_name.to_bytes(),
+            fn_name: self.fn_name.to_bytes(),
+            request_bytes: &self.req[..],
+            seq_id: self.seq_id,
+            interaction_id: self.interaction_id,
+        };
+        self.configuration
+            .response_handler
+            .make_sink_complete_bytes(&ctx)
+    }
+
+    pub(crate) fn get_configuration(&self) -> &AsyncTransportConfiguration<H> {
+        &self.configuration
+    }
+
+    // Writes `frame[*written..]` on the call's connection, within the write timeout.
+    pub(crate) fn poll_write_frame(
+        &mut self,
+        cx: &mut Context,
+        frame: &[u8],
+        written: &mut usize,
+    ) -> Poll<Result<(), IoError>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.enter_span();
+
+        let stream = &mut match self.stream.lock() {
             Ok(stream) => stream,
-            Err(err) => {
-                return Poll::Ready(Err(IoError::new(IoErrorKind::Other, err.to_string()).into()))
+            Err(err) => return Poll::Ready(Err(IoError::other(err.to_string()))),
+        };
+        let write_timeout = self.configuration.get_write_timeout();
+
+        while *written < frame.len() {
+            let write_timeout_future = self
+                .write_timeout_future
+                .get_or_insert_with(|| SLEEP::sleep(write_timeout).wait());
+            let ret = ready!(async_write_poll(
+                &mut **stream,
+                &frame[*written..],
+                write_timeout_future,
+                cx,
+            ));
+            self.write_timeout_future = None;
+
+            let n = match ret {
+                Ok(n) => n,
+                Err(err) => {
+                    self.on_io_error(&err);
+                    return Poll::Ready(Err(err));
+                }
+            };
+            if n == 0 {
+                return Poll::Ready(Err(IoErrorKind::WriteZero.into()));
+            }
+            *written += n;
+            if let Some(counters) = &self.counters {
+                counters.on_written(n);
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    pub(crate) fn poll_flush_frames(&mut self, cx: &mut Context) -> Poll<Result<(), IoError>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.enter_span();
+
+        let stream = &mut match self.stream.lock() {
+            Ok(stream) => stream,
+            Err(err) => return Poll::Ready(Err(IoError::other(err.to_string()))),
+        };
+        let write_timeout = self.configuration.get_write_timeout();
+        let write_timeout_future = self
+            .write_timeout_future
+            .get_or_insert_with(|| SLEEP::sleep(write_timeout).wait());
+
+        let ret = match Pin::new(&mut **stream).poll_flush(cx) {
+            Poll::Ready(ret) => ret,
+            Poll::Pending => match write_timeout_future.as_mut().poll(cx) {
+                Poll::Ready(_) => Err(IoError::new(IoErrorKind::TimedOut, "write timeout")),
+                Poll::Pending => return Poll::Pending,
+            },
+        };
+        self.write_timeout_future = None;
+
+        if let Err(err) = &ret {
+            self.on_io_error(err);
+        }
+        Poll::Ready(ret)
+    }
+
+    fn on_io_error(&self, err: &IoError) {
+        if let Some(counters) = &self.counters {
+            counters.on_io_error(err);
+        }
+    }
+}
+
+impl<S, SLEEP, H> Drop for Call<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn drop(&mut self) {
+        if self.in_flight {
+            if let Some(counters) = &self.counters {
+                counters.on_call_cancel();
+            }
+        }
+    }
+}
+
+impl<S, SLEEP, H> Call<S, SLEEP, H>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    SLEEP: Sleepble,
+    H: ResponseHandlerV2 + Unpin,
+{
+    fn poll_call(&mut self, cx: &mut Context) -> Poll<<Self as Future>::Output> {
+        let this = self;
+
+        if this.state == CallState::Pending {
+            if let (Some(slot), None) = (&this.slot, &this.slot_guard) {
+                let slot_lock = this
+                    .slot_lock
+                    .get_or_insert_with(|| Box::pin(slot.lock_arc()));
+                this.slot_guard = Some(ready!(slot_lock.as_mut().poll(cx)));
+                this.slot_lock = None;
+            }
+
+            if let Some(closed) = &this.closed {
+                if closed.load(Ordering::Acquire) {
+                    return Poll::Ready(Err(closed_error().into()));
+                }
             }
+
+            if let Some(desynced) = &this.desynced {
+                if desynced.load(Ordering::Acquire) {
+                    return Poll::Ready(Err(IoError::other(
+                        "Connection desynchronized by a cancelled stream",
+                    )
+                    .into()));
+                }
+            }
+
+            if let Some((current, generation)) = &this.generation {
+                if current.load(Ordering::Acquire) != *generation {
+                    return Poll::Ready(Err(interaction_lost_error().into()));
+                }
+            }
+
+            if let Some(busy) = &this.busy {
+                if busy.load(Ordering::Acquire) {
+                    return Poll::Ready(Err(
+                        IoError::other("Connection busy with an open stream").into()
+                    ));
+                }
+            }
+
+            if !this.rpc_options.headers.is_empty() {
+                let headers = core::mem::take(&mut this.rpc_options.headers);
+                let ctx = CallContext {
+                    service_name: this.service_name.to_bytes(),
+                    fn_name: this.fn_name.to_bytes(),
+                    request_bytes: &this.req[..],
+                    seq_id: this.seq_id,
+                    interaction_id: this.interaction_id,
+                };
+                if let Some(req) = this
+                    .configuration
+                    .response_handler
+                    .make_request_bytes_with_headers(&ctx, &headers)?
+                {
+                    this.req = Bytes::from(req);
+                }
+            }
+
+            if this.prelude.is_none() {
+                this.prelude = this
+                    .pending_frames
+                    .as_ref()
+                    .and_then(|pending_frames| pending_frames.lock().ok())
+                    .map(|mut pending_frames| core::mem::take(&mut *pending_frames))
+                    .filter(|frames| !frames.is_empty())
+                    .map(|frames| (frames, 0));
+            }
+            if let Some((frames, mut written)) = this.prelude.take() {
+                match this.poll_write_frame(cx, &frames, &mut written) {
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err.into())),
+                    Poll::Pending => {
+                        this.prelude = Some((frames, written));
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+
+        if this.state < CallState::Writed {
+            let req = this.req.clone();
+            let mut written = this.req_written;
+            let ret = this.poll_write_frame(cx, &req, &mut written);
+            this.req_written = written;
+            ready!(ret)?;
+
+            this.state = CallState::Writed;
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(request_size = req.len(), "write done");
+
+            if let Some(wire_dump) = this.configuration.get_wire_dump() {
+                wire_dump.dump(
+                    WireDumpKind::Request,
+                    this.service_name,
+                    this.fn_name,
+                    0,
+                    &req[..],
+                );
+            }
+
+            if let Some(observer) = this.configuration.get_call_observer() {
+                observer.on_write_done(
+                    this.service_name,
+                    this.fn_name,
+                    req.len(),
+                    this.started_at.elapsed(),
+                );
+            }
+        }
+
+        if this.rpc_options.oneway {
+            ready!(this.poll_flush_frames(cx))?;
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!("oneway call flushed");
+
+            return Poll::Ready(Ok(Cursor::new(Bytes::new())));
+        }
+
+        let stream = &mut match this.stream.lock() {
+            Ok(stream) => stream,
+            Err(err) => return Poll::Ready(Err(IoError::other(err.to_string()).into())),
         };
         let service_name = &this.service_name;
         let fn_name = &this.fn_name;
@@ -212,74 +1303,205 @@ where
         let buf_storage = &mut this.buf_storage;
         let parsed_response_bytes_count = &mut this.parsed_response_bytes_count;
 
-        if this.state < CallState::Writed {
-            let mut write_future = stream.write_all(&req[..]);
-            ready!(Pin::new(&mut write_future).poll(cx))?;
+        let ctx = CallContext {
+            service_name: this.service_name.to_bytes(),
+            fn_name: this.fn_name.to_bytes(),
+            request_bytes: &req[..],
+            seq_id: this.seq_id,
+            interaction_id: this.interaction_id,
+        };
 
-            this.state = CallState::Writed;
+        if this.state < CallState::Reading {
+            let static_res_buf = ready!(configuration
+                .response_handler
+                .poll_static_response_bytes(cx, &ctx))?;
+            if let Some(static_res_buf) = static_res_buf {
+                debug_assert!(buf_storage.is_empty(), "The buf_storage should empty");
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    response_size = static_res_buf.len(),
+                    "made static response bytes"
+                );
+                return Poll::Ready(Ok(Cursor::new(Bytes::from(static_res_buf))));
+            }
+
+            this.state = CallState::Reading;
         }
 
-        let static_res_buf = configuration
-            .response_handler
-            .try_make_static_response_bytes(
-                service_name.to_bytes(),
-                fn_name.to_bytes(),
-                &req[..],
-            )?;
-        if let Some(static_res_buf) = static_res_buf {
-            debug_assert!(buf_storage.is_empty(), "The buf_storage should empty");
-            return Poll::Ready(Ok(Cursor::new(Bytes::from(static_res_buf))));
-        }
-
-        let mut buf = vec![0u8; configuration.get_buf_size()];
-        let n_de;
-        loop {
-            let mut read_future =
-                stream.read_with_timeout::<SLEEP>(&mut buf, configuration.get_read_timeout());
-            let n = ready!(Pin::new(&mut read_future).poll(cx))?;
+        let (n_de, payload) = loop {
+            if !this.parse_pending {
+                let read_timeout = configuration.get_read_timeout();
+                let read_timeout_future = this
+                    .read_timeout_future
+                    .get_or_insert_with(|| SLEEP::sleep(read_timeout).wait());
 
-            if n == 0 {
-                *parsed_response_bytes_count += 1;
-                if *parsed_response_bytes_count > configuration.get_max_parse_response_bytes_count()
-                {
-                    return Poll::Ready(Err(IoError::new(
-                        IoErrorKind::Other,
-                        "Reach max parse response bytes count",
-                    )
-                    .into()));
+                if buf_storage.capacity() == 0 {
+                    if let Some(pool) = configuration.get_buffer_pool() {
+                        *buf_storage = pool.get(configuration.get_buf_size());
+                    }
                 }
-                continue;
-            }
 
-            buf_storage.extend_from_slice(&buf[..n]);
+                // Reads directly into buf_storage, doubling it each time up to max_buf_size.
+                let len = this.filled_len.take().unwrap_or(buf_storage.len());
+                let read_len = configuration
+                    .get_buf_size()
+                    .max(len)
+                    .max(this.parse_at_len.saturating_sub(len))
+                    .min(configuration.get_max_buf_size() - len);
+                if buf_storage.len() != len + read_len {
+                    buf_storage.resize(len + read_len, 0);
+                }
+                let ret = async_read_poll(
+                    &mut **stream,
+                    &mut buf_storage[len..],
+                    read_timeout_future,
+                    cx,
+                );
+                let n = match ret {
+                    Poll::Ready(Ok(n)) => n,
+                    Poll::Ready(Err(err)) => {
+                        buf_storage.truncate(len);
+                        if let Some(counters) = &this.counters {
+                            counters.on_io_error(&err);
+                        }
+                        return Poll::Ready(Err(err.into()));
+                    }
+                    Poll::Pending => {
+                        this.filled_len = Some(len);
+                        return Poll::Pending;
+                    }
+                };
+                buf_storage.truncate(len + n);
+                this.read_timeout_future = None;
 
-            if let Some(n) = configuration
-                .response_handler
-                .parse_response_bytes(buf_storage)?
-            {
-                n_de = n;
-                break;
-            } else {
-                if buf_storage.len() >= configuration.get_max_buf_size() {
-                    return Poll::Ready(Err(IoError::new(
-                        IoErrorKind::Other,
-                        "Reach max buffer size",
-                    )
-                    .into()));
+                if let Some(counters) = &this.counters {
+                    counters.on_read(n);
                 }
 
-                *parsed_response_bytes_count += 1;
-                if *parsed_response_bytes_count > configuration.get_max_parse_response_bytes_count()
-                {
-                    return Poll::Ready(Err(IoError::new(
-                        IoErrorKind::Other,
-                        "Reach max parse response bytes count",
-                    )
-                    .into()));
+                #[cfg(feature = "tracing")]
+                tracing::trace!(chunk_size = n, "read chunk");
+
+                if n == 0 {
+                    *parsed_response_bytes_count += 1;
+                    if *parsed_response_bytes_count
+                        > configuration.get_max_parse_response_bytes_count()
+                    {
+                        return Poll::Ready(Err(IoError::other(
+                            "Reach max parse response bytes count",
+                        )
+                        .into()));
+                    }
+                    continue;
                 }
+
+                if !this.first_byte_observed {
+                    this.first_byte_observed = true;
+                    if let Some(observer) = configuration.get_call_observer() {
+                        observer.on_first_byte(service_name, fn_name, this.started_at.elapsed());
+                    }
+                }
+
+                if buf_storage.len() < this.parse_at_len {
+                    continue;
+                }
+
+                if let Some(wire_dump) = configuration.get_wire_dump() {
+                    wire_dump.dump(
+                        WireDumpKind::ParseAttempt,
+                        service_name,
+                        fn_name,
+                        this.parsed_len,
+                        &buf_storage[this.parsed_len..],
+                    );
+                }
+
+                this.parse_pending = true;
             }
+
+            let parsed = ready!(configuration.response_handler.poll_parse_response_bytes(
+                cx,
+                &ctx,
+                &buf_storage[..],
+                this.parsed_len,
+            ))?;
+            this.parse_pending = false;
+            this.parsed_len = buf_storage.len();
+
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                buf_len = buf_storage.len(),
+                parsed = ?parsed,
+                attempt = *parsed_response_bytes_count + 1,
+                "parse response bytes"
+            );
+
+            match parsed {
+                ResponseParseOutcome::Complete(n) => break (n, None),
+                ResponseParseOutcome::Rewrite { len, payload } => break (len, Some(payload)),
+                ResponseParseOutcome::NeedMore(n) => {
+                    this.parse_at_len = buf_storage.len().saturating_add(n.max(1));
+                    if buf_storage.len() >= configuration.get_max_buf_size()
+                        || this.parse_at_len > configuration.get_max_buf_size()
+                    {
+                        return Poll::Ready(Err(IoError::other("Reach max buffer size").into()));
+                    }
+
+                    *parsed_response_bytes_count += 1;
+                    if *parsed_response_bytes_count
+                        > configuration.get_max_parse_response_bytes_count()
+                    {
+                        return Poll::Ready(Err(IoError::other(
+                            "Reach max parse response bytes count",
+                        )
+                        .into()));
+                    }
+                }
+            }
+        };
+
+        if n_de > buf_storage.len() {
+            return Poll::Ready(Err(IoError::other("Parsed response out of range").into()));
+        }
+
+        if this.response_headers.is_none() {
+            this.response_headers = Some(
+                configuration
+                    .response_handler
+                    .parse_response_headers(&ctx, &buf_storage[..n_de])?,
+            );
         }
 
-        Poll::Ready(Ok(Cursor::new(Bytes::from(buf_storage[..n_de].to_vec()))))
+        if this.keep_remaining {
+            let res = buf_storage.split_to(n_de).freeze();
+            return Poll::Ready(Ok(Cursor::new(payload.map(Bytes::from).unwrap_or(res))));
+        }
+
+        if let Some(wire_dump) = configuration.get_wire_dump() {
+            if n_de < buf_storage.len() {
+                wire_dump.dump(
+                    WireDumpKind::Discarded,
+                    service_name,
+                    fn_name,
+                    n_de,
+                    &buf_storage[n_de..],
+                );
+            }
+        }
+
+        if let Some(payload) = payload {
+            return Poll::Ready(Ok(Cursor::new(Bytes::from(payload))));
+        }
+
+        // With a buffer pool, small responses are copied so that the whole buffer goes back to
+        // the pool, larger ones keep the zero-copy split.
+        if configuration.get_buffer_pool().is_some() && n_de <= configuration.get_buf_size() {
+            return Poll::Ready(Ok(Cursor::new(Bytes::copy_from_slice(
+                &buf_storage[..n_de],
+            ))));
+        }
+
+        Poll::Ready(Ok(Cursor::new(
+            core::mem::take(buf_storage).split_to(n_de).freeze(),
+        )))
     }
 }
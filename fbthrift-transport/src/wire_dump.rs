@@ -0,0 +1,276 @@
+use core::{ffi::CStr, fmt::Write as _};
+use std::{borrow::Cow, sync::Arc};
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireDumpKind {
+    Request,
+    ParseAttempt,
+    Discarded,
+}
+
+impl WireDumpKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Request => "request",
+            Self::ParseAttempt => "parse_attempt",
+            Self::Discarded => "discarded",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WireDumpContext {
+    pub kind: WireDumpKind,
+    pub service_name: &'static CStr,
+    pub fn_name: &'static CStr,
+    // Where the dumped bytes start in the request or the read buffer. Parse attempts only dump
+    // what was read since the previous attempt.
+    pub offset: usize,
+    pub len: usize,
+}
+
+//
+pub trait WireDumpWriter: Send + Sync {
+    fn redact<'a>(&self, _ctx: &WireDumpContext, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        Cow::Borrowed(bytes)
+    }
+
+    fn write(&self, ctx: &WireDumpContext, dump: &str);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopWireDumpWriter;
+
+impl WireDumpWriter for NoopWireDumpWriter {
+    fn write(&self, _ctx: &WireDumpContext, _dump: &str) {}
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrWireDumpWriter;
+
+impl WireDumpWriter for StderrWireDumpWriter {
+    fn write(&self, ctx: &WireDumpContext, dump: &str) {
+        eprintln!(
+            "fbthrift_transport {} {}.{} {} bytes at {}\n{}",
+            ctx.kind.as_str(),
+            ctx.service_name.to_string_lossy(),
+            ctx.fn_name.to_string_lossy(),
+            ctx.len,
+            ctx.offset,
+            dump
+        );
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingWireDumpWriter;
+
+#[cfg(feature = "tracing")]
+impl WireDumpWriter for TracingWireDumpWriter {
+    fn write(&self, ctx: &WireDumpContext, dump: &str) {
+        tracing::debug!(
+            kind = ctx.kind.as_str(),
+            service_name = %ctx.service_name.to_string_lossy(),
+            fn_name = %ctx.fn_name.to_string_lossy(),
+            offset = ctx.offset,
+            len = ctx.len,
+            "wire dump\n{dump}"
+        );
+    }
+}
+
+//
+#[derive(Clone)]
+pub struct WireDump {
+    max_bytes: usize,
+    writer: Arc<dyn WireDumpWriter>,
+}
+
+impl core::fmt::Debug for WireDump {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WireDump")
+            .field("max_bytes", &self.max_bytes)
+            .finish()
+    }
+}
+
+// Writes to tracing with the `tracing` feature, nowhere otherwise.
+impl Default for WireDump {
+    fn default() -> Self {
+        #[cfg(feature = "tracing")]
+        let writer = Arc::new(TracingWireDumpWriter);
+        #[cfg(not(feature = "tracing"))]
+        let writer = Arc::new(NoopWireDumpWriter);
+
+        Self::new(writer)
+    }
+}
+
+impl WireDump {
+    pub fn new(writer: Arc<dyn WireDumpWriter>) -> Self {
+        Self {
+            max_bytes: 1024,
+            writer,
+        }
+    }
+
+    pub fn set_max_bytes(&mut self, size: usize) {
+        self.max_bytes = size;
+    }
+
+    pub fn get_max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    pub(crate) fn dump(
+        &self,
+        kind: WireDumpKind,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        offset: usize,
+        bytes: &[u8],
+    ) {
+        let ctx = WireDumpContext {
+            kind,
+            service_name,
+            fn_name,
+            offset,
+            len: bytes.len(),
+        };
+
+        let bytes = self.writer.redact(&ctx, bytes);
+        let mut dump = hexdump_at(offset, &bytes[..bytes.len().min(self.max_bytes)]);
+        if bytes.len() > self.max_bytes {
+            let _ = writeln!(dump, "... {} bytes truncated", bytes.len() - self.max_bytes);
+        }
+
+        self.writer.write(&ctx, &dump);
+    }
+}
+
+//
+pub fn hexdump(bytes: &[u8]) -> String {
+    hexdump_at(0, bytes)
+}
+
+fn hexdump_at(offset: usize, bytes: &[u8]) -> String {
+    let mut s = String::with_capacity((bytes.len() / 16 + 2) * 79);
+
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(s, "{:08x}  ", offset + i * 16);
+        for j in 0..16 {
+            match line.get(j) {
+                Some(b) => {
+                    let _ = write!(s, "{b:02x} ");
+                }
+                None => s.push_str("   "),
+            }
+            if j == 7 {
+                s.push(' ');
+            }
+        }
+        s.push_str(" |");
+        for b in line {
+            s.push(if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            });
+        }
+        s.push_str("|\n");
+    }
+    if !bytes.is_empty() {
+        let _ = writeln!(s, "{:08x}", offset + bytes.len());
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(hexdump(b""), "");
+        assert_eq!(
+            hexdump(b"abc\n"),
+            "00000000  61 62 63 0a                                       |abc.|\n00000004\n"
+        );
+        assert_eq!(
+            hexdump(b"0123456789abcdef\x00"),
+            "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n00000010  00                                                |.|\n00000011\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_with_redact_and_max_bytes() {
+        #[derive(Default)]
+        struct FooWireDumpWriter(Mutex<Vec<(WireDumpKind, String)>>);
+
+        impl WireDumpWriter for FooWireDumpWriter {
+            fn redact<'a>(&self, _ctx: &WireDumpContext, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+                Cow::Owned(bytes.iter().map(|_| b'x').collect())
+            }
+
+            fn write(&self, ctx: &WireDumpContext, dump: &str) {
+                self.0.lock().unwrap().push((ctx.kind, dump.to_owned()));
+            }
+        }
+
+        let writer = Arc::new(FooWireDumpWriter::default());
+        let mut d = WireDump::new(writer.clone());
+        d.set_max_bytes(2);
+
+        d.dump(WireDumpKind::Request, c"my_service", c"my_fn", 0, b"secret");
+
+        let records = writer.0.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0, WireDumpKind::Request);
+        assert_eq!(
+            records[0].1,
+            "00000000  78 78                                             |xx|\n00000002\n... 4 bytes truncated\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_at_offset() {
+        #[derive(Default)]
+        struct FooWireDumpWriter(Mutex<Vec<(usize, usize, String)>>);
+
+        impl WireDumpWriter for FooWireDumpWriter {
+            fn write(&self, ctx: &WireDumpContext, dump: &str) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((ctx.offset, ctx.len, dump.to_owned()));
+            }
+        }
+
+        let writer = Arc::new(FooWireDumpWriter::default());
+        let d = WireDump::new(writer.clone());
+
+        d.dump(
+            WireDumpKind::ParseAttempt,
+            c"my_service",
+            c"my_fn",
+            18,
+            b"ab",
+        );
+
+        let records = writer.0.lock().unwrap();
+        assert_eq!(
+            records[0],
+            (
+                18,
+                2,
+                "00000012  61 62                                             |ab|\n00000014\n"
+                    .to_owned()
+            )
+        );
+    }
+}
@@ -0,0 +1,280 @@
+#![cfg(feature = "impl_tokio")]
+
+use core::time::Duration;
+use std::{
+    io::Error as IoError,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{
+    connector::Connector,
+    fbthrift_transport_response_handler::MockResponseHandler,
+    impl_tokio::{tcp_connector, TokioSleep, TokioTcpStream},
+    AsyncTransportConfiguration, BalanceStrategy, BalancedTransport,
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+fn echo_server(listener: TcpListener) -> JoinHandle<Result<(), IoError>> {
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            tokio::spawn(async move {
+                let mut buf = vec![0; 5];
+                loop {
+                    stream.read_exact(&mut buf).await?;
+                    stream.write_all(&buf).await?;
+                }
+                #[allow(unreachable_code)]
+                Result::<_, IoError>::Ok(())
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn eject_and_probe_back() -> Result<(), Box<dyn std::error::Error>> {
+    let listener_1 = TcpListener::bind("127.0.0.1:0").await?;
+    let addr_1 = listener_1.local_addr()?;
+    // Nothing listens on addr_1 for now.
+    drop(listener_1);
+
+    let listener_2 = TcpListener::bind("127.0.0.1:0").await?;
+    let addr_2 = listener_2.local_addr()?;
+    let server_2 = echo_server(listener_2);
+
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> = vec![
+        Arc::new(tcp_connector(addr_1)),
+        Arc::new(tcp_connector(addr_2)),
+    ];
+    let transport = BalancedTransport::<_, TokioSleep, _>::with_eject_duration(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        BalanceStrategy::RoundRobin,
+        Duration::from_millis(200),
+    );
+    assert_eq!(transport.endpoints_len(), 2);
+
+    let mut n_err = 0;
+    for _ in 0..5 {
+        match transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("abcde"),
+                Default::default(),
+            )
+            .await
+        {
+            Ok(out) => assert_eq!(out.into_inner(), Bytes::from("abcde")),
+            Err(_) => n_err += 1,
+        }
+    }
+    assert_eq!(n_err, 1);
+    assert_eq!(transport.healthy_endpoints_len(), 1);
+
+    //
+    let listener_1 = TcpListener::bind(addr_1).await?;
+    let server_1 = echo_server(listener_1);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    for _ in 0..4 {
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("abcde"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("abcde"));
+    }
+    assert_eq!(transport.healthy_endpoints_len(), 2);
+
+    server_1.abort();
+    server_2.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn strategies() -> Result<(), Box<dyn std::error::Error>> {
+    for strategy in [
+        BalanceStrategy::RoundRobin,
+        BalanceStrategy::LeastOutstanding,
+        BalanceStrategy::PowerOfTwoChoices,
+    ] {
+        let mut connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> = vec![];
+        let mut servers = vec![];
+        for _ in 0..3 {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            connectors.push(Arc::new(tcp_connector(listener.local_addr()?)));
+            servers.push(echo_server(listener));
+        }
+
+        let transport = BalancedTransport::<_, TokioSleep, _>::new(
+            connectors,
+            AsyncTransportConfiguration::new(MockResponseHandler),
+            strategy,
+        );
+        assert_eq!(transport.get_strategy(), strategy);
+
+        for _ in 0..6 {
+            let out = transport
+                .call(
+                    c"my_service",
+                    c"my_fn",
+                    Bytes::from("abcde"),
+                    Default::default(),
+                )
+                .await?;
+            assert_eq!(out.into_inner(), Bytes::from("abcde"));
+        }
+
+        let calls_succeeded = transport
+            .endpoints_stats()
+            .iter()
+            .flatten()
+            .map(|stats| stats.calls_succeeded)
+            .sum::<u64>();
+        assert_eq!(calls_succeeded, 6);
+
+        for server in servers {
+            server.abort();
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_calls_on_one_endpoint() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let connects = Arc::new(AtomicUsize::new(0));
+
+    // Replies after a delay, so that concurrent requests would be in flight together.
+    let server = tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            tokio::spawn(async move {
+                let mut buf = vec![0; 5];
+                loop {
+                    stream.read_exact(&mut buf).await?;
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    stream.write_all(&buf).await?;
+                }
+                #[allow(unreachable_code)]
+                Result::<_, IoError>::Ok(())
+            });
+        }
+        #[allow(unreachable_code)]
+        Result::<_, IoError>::Ok(())
+    });
+
+    let connector_connects = connects.clone();
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> = vec![Arc::new(move || {
+        connector_connects.fetch_add(1, Ordering::SeqCst);
+        tcp_connector(addr).connect()
+    })];
+    let transport = BalancedTransport::<_, TokioSleep, _>::new(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        BalanceStrategy::LeastOutstanding,
+    );
+
+    let (out_1, out_2) = tokio::join!(
+        transport.call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("abcde"),
+            Default::default(),
+        ),
+        transport.call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("fghij"),
+            Default::default(),
+        )
+    );
+    assert_eq!(out_1?.into_inner(), Bytes::from("abcde"));
+    assert_eq!(out_2?.into_inner(), Bytes::from("fghij"));
+    assert_eq!(connects.load(Ordering::SeqCst), 1);
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn probe_again_after_dropped_probe() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    // Nothing listens on addr for now.
+    drop(listener);
+
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> = vec![Arc::new(tcp_connector(addr))];
+    let transport = BalancedTransport::<_, TokioSleep, _>::with_eject_duration(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        BalanceStrategy::RoundRobin,
+        Duration::from_millis(100),
+    );
+
+    assert!(transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await
+        .is_err());
+    assert_eq!(transport.healthy_endpoints_len(), 0);
+
+    // The first connection never replies, the next ones echo.
+    let listener = TcpListener::bind(addr).await?;
+    let server = tokio::spawn(async move {
+        let (silent, _) = listener.accept().await?;
+        echo_server(listener).await??;
+        drop(silent);
+        Result::<_, IoError>::Ok(())
+    });
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    // The probe is dropped on the timeout.
+    assert!(tokio::time::timeout(
+        Duration::from_millis(100),
+        transport.call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+    )
+    .await
+    .is_err());
+
+    let out = transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("fghij"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("fghij"));
+    assert_eq!(transport.healthy_endpoints_len(), 1);
+
+    server.abort();
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use core::{ffi::CStr, time::Duration};
+use std::{
+    io::{Cursor, Error as IoError, ErrorKind as IoErrorKind},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bytes::{Bytes, BytesMut};
+use fbthrift::{ClientStreamElement, Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use fbthrift_transport::{
+    circuit_breaker::{CircuitOpenError, CircuitState},
+    CircuitBreakerConfiguration, CircuitBreakerTransport,
+};
+use futures_lite::future::block_on;
+use futures_util::{
+    future::{self, BoxFuture},
+    stream::{self, BoxStream},
+    StreamExt as _,
+};
+
+#[derive(Default)]
+struct FooTransport {
+    failing: Arc<AtomicBool>,
+    calls: Arc<AtomicUsize>,
+}
+
+impl Framing for FooTransport {
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        BytesMut::with_capacity(cap)
+    }
+}
+
+impl Transport for FooTransport {
+    type RpcOptions = ();
+
+    fn call(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        _rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(future::ready(if self.failing.load(Ordering::SeqCst) {
+            Err(IoError::new(IoErrorKind::ConnectionReset, "").into())
+        } else {
+            Ok(Cursor::new(req))
+        }))
+    }
+
+    fn call_stream(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        _rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<
+        'static,
+        anyhow::Result<(
+            FramingDecoded<Self>,
+            BoxStream<'static, anyhow::Result<ClientStreamElement<FramingDecoded<Self>>>>,
+        )>,
+    > {
+        let call = self.call(_service_name, _fn_name, req, ());
+        Box::pin(async move {
+            let res = call.await?;
+            let elements = vec![Ok(ClientStreamElement::Reply(res.clone()))];
+            Ok((res, stream::iter(elements).boxed()))
+        })
+    }
+
+    fn create_interaction(&self, _method_name: &'static CStr) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            failing: self.failing.clone(),
+            calls: self.calls.clone(),
+        })
+    }
+}
+
+#[test]
+fn open_half_open_and_close() {
+    let inner = FooTransport::default();
+    let failing = inner.failing.clone();
+    let calls = inner.calls.clone();
+
+    let mut c = CircuitBreakerConfiguration::new();
+    c.set_minimum_calls(4);
+    c.set_window_size(4);
+    c.set_failure_rate_threshold(0.5);
+    c.set_cool_down(Duration::from_millis(50));
+    let transport = CircuitBreakerTransport::new(inner, c);
+
+    let call = || block_on(transport.call(c"my_service", c"my_fn", Bytes::from("foo"), ()));
+
+    //
+    assert!(call().is_ok());
+    assert!(call().is_ok());
+    failing.store(true, Ordering::SeqCst);
+    assert!(call().is_err());
+    assert_eq!(
+        transport.state(c"my_service", c"my_fn"),
+        CircuitState::Closed
+    );
+    assert!(call().is_err());
+    assert_eq!(transport.state(c"my_service", c"my_fn"), CircuitState::Open);
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // Fails fast while open.
+    let err = call().expect_err("");
+    assert!(err.downcast_ref::<CircuitOpenError>().is_some());
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // Half-open probe fails, then opens again.
+    std::thread::sleep(Duration::from_millis(60));
+    let err = call().expect_err("");
+    assert!(err.downcast_ref::<CircuitOpenError>().is_none());
+    assert_eq!(transport.state(c"my_service", c"my_fn"), CircuitState::Open);
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+
+    // Half-open probe succeeds, then closes.
+    failing.store(false, Ordering::SeqCst);
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(call().is_ok());
+    assert_eq!(
+        transport.state(c"my_service", c"my_fn"),
+        CircuitState::Closed
+    );
+    assert!(call().is_ok());
+}
+
+#[test]
+fn per_function() {
+    let inner = FooTransport::default();
+    inner.failing.store(true, Ordering::SeqCst);
+
+    let mut c = CircuitBreakerConfiguration::new();
+    c.set_minimum_calls(1);
+    c.set_per_function(true);
+    let transport = CircuitBreakerTransport::new(inner, c);
+
+    assert!(block_on(transport.call(c"my_service", c"fn_a", Bytes::from("foo"), ())).is_err());
+    assert_eq!(transport.state(c"my_service", c"fn_a"), CircuitState::Open);
+    assert_eq!(
+        transport.state(c"my_service", c"fn_b"),
+        CircuitState::Closed
+    );
+}
+
+#[test]
+fn call_stream_and_interaction() {
+    let inner = FooTransport::default();
+    let failing = inner.failing.clone();
+    let calls = inner.calls.clone();
+
+    let mut c = CircuitBreakerConfiguration::new();
+    c.set_minimum_calls(2);
+    c.set_window_size(2);
+    c.set_failure_rate_threshold(0.5);
+    let transport = CircuitBreakerTransport::new(inner, c);
+    let interaction = transport.create_interaction(c"MyInteraction").expect("");
+
+    let (res, stream) =
+        block_on(transport.call_stream(c"my_service", c"my_fn", Bytes::from("foo"), ())).expect("");
+    assert_eq!(res.into_inner(), Bytes::from("foo"));
+    assert_eq!(block_on(stream.collect::<Vec<_>>()).len(), 1);
+
+    // Counted by the breaker shared with the interaction.
+    failing.store(true, Ordering::SeqCst);
+    assert!(block_on(interaction.call(c"my_service", c"my_fn", Bytes::from("foo"), ())).is_err());
+    assert_eq!(transport.state(c"my_service", c"my_fn"), CircuitState::Open);
+
+    let err = block_on(transport.call_stream(c"my_service", c"my_fn", Bytes::from("foo"), ()))
+        .err()
+        .expect("");
+    assert!(err.downcast_ref::<CircuitOpenError>().is_some());
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
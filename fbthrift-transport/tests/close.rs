@@ -0,0 +1,214 @@
+#![cfg(feature = "impl_tokio")]
+
+use core::task::{Context, Poll};
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{AsyncTransport, AsyncTransportConfiguration};
+use fbthrift_transport_response_handler::{
+    CallContext, ResponseHandlerV2, ResponseParseOutcome, StreamElementKind,
+};
+use futures_util::StreamExt as _;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpListener,
+    task::JoinHandle,
+    time::sleep,
+};
+
+// 5 bytes frames, the credits frame is `end!!`, so the echoing server ends the stream.
+#[derive(Clone)]
+struct FooResponseHandler;
+
+impl ResponseHandlerV2 for FooResponseHandler {
+    fn poll_static_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+    ) -> Poll<Result<Option<Vec<u8>>, IoError>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn poll_parse_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+        response_bytes: &[u8],
+        _new_bytes_from: usize,
+    ) -> Poll<Result<ResponseParseOutcome, IoError>> {
+        Poll::Ready(Ok(match response_bytes.len() {
+            n if n >= 5 => ResponseParseOutcome::Complete(5),
+            n => ResponseParseOutcome::NeedMore(5 - n),
+        }))
+    }
+
+    fn stream_element_kind(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        element_bytes: &[u8],
+    ) -> Result<StreamElementKind, IoError> {
+        Ok(match element_bytes {
+            b"end!!" => StreamElementKind::End,
+            _ => StreamElementKind::Reply,
+        })
+    }
+
+    fn make_stream_credits_bytes(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        _credits: u32,
+    ) -> Option<Vec<u8>> {
+        Some(b"end!!".to_vec())
+    }
+}
+
+// Echoes 5 bytes frames after `delay`, sets `eof` once the client shut its write half down.
+async fn server(delay: Duration) -> Result<(SocketAddr, Arc<AtomicBool>, JoinHandle<()>), IoError> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let eof = Arc::new(AtomicBool::new(false));
+
+    let server_eof = eof.clone();
+    let handle = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("");
+        let mut buf = vec![0; 5];
+        loop {
+            match stream.read_exact(&mut buf).await {
+                Ok(_) => {}
+                Err(err) if err.kind() == IoErrorKind::UnexpectedEof => {
+                    server_eof.store(true, Ordering::SeqCst);
+                    break;
+                }
+                Err(err) => panic!("{err}"),
+            }
+            sleep(delay).await;
+            if stream.write_all(&buf).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok((addr, eof, handle))
+}
+
+#[tokio::test]
+async fn close_waits_for_in_flight_calls() -> Result<(), Box<dyn std::error::Error>> {
+    let (addr, eof, server) = server(Duration::from_millis(100)).await?;
+
+    let transport = AsyncTransport::with_tokio_tcp_connect(
+        addr,
+        AsyncTransportConfiguration::new(FooResponseHandler),
+    )
+    .await?;
+
+    let call = transport.call(
+        c"my_service",
+        c"my_fn",
+        Bytes::from("abcde"),
+        Default::default(),
+    );
+    let (out, closed) = tokio::join!(call, async {
+        sleep(Duration::from_millis(20)).await;
+        transport.close(Duration::from_secs(1)).await
+    });
+    assert_eq!(out?.into_inner(), Bytes::from("abcde"));
+    closed?;
+    assert!(transport.is_closed());
+    assert_eq!(transport.stats().in_flight, 0);
+
+    let err = transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("fghij"),
+            Default::default(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Transport closed");
+    assert!(transport.reconnect().await.is_err());
+
+    server.await?;
+    assert!(eof.load(Ordering::SeqCst));
+
+    // Closing again is a no-op.
+    transport.close(Duration::from_secs(1)).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    let (addr, _, server) = server(Duration::from_secs(10)).await?;
+
+    let transport = AsyncTransport::with_tokio_tcp_connect(
+        addr,
+        AsyncTransportConfiguration::new(FooResponseHandler),
+    )
+    .await?;
+
+    let call = transport.call(
+        c"my_service",
+        c"my_fn",
+        Bytes::from("abcde"),
+        Default::default(),
+    );
+    let (_, closed) = tokio::join!(
+        tokio::time::timeout(Duration::from_millis(300), call),
+        async {
+            sleep(Duration::from_millis(20)).await;
+            transport.close(Duration::from_millis(100)).await
+        }
+    );
+    assert_eq!(closed.unwrap_err().kind(), IoErrorKind::TimedOut);
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_waits_for_open_stream() -> Result<(), Box<dyn std::error::Error>> {
+    let (addr, eof, server) = server(Duration::from_millis(50)).await?;
+
+    let mut c = AsyncTransportConfiguration::new(FooResponseHandler);
+    c.set_stream_credits(1);
+    let transport = AsyncTransport::with_tokio_tcp_connect(addr, c).await?;
+
+    let (res, mut stream) = transport
+        .open_stream(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(res.into_inner(), Bytes::from("abcde"));
+    assert_eq!(transport.stats().in_flight, 1);
+
+    // The credits frame is written after the close started, before the shutdown.
+    let (closed, ended) = tokio::join!(transport.close(Duration::from_secs(1)), async {
+        sleep(Duration::from_millis(20)).await;
+        while let Some(element) = stream.next().await {
+            element?;
+        }
+        anyhow::Ok(())
+    });
+    ended?;
+    closed?;
+    assert_eq!(transport.stats().in_flight, 0);
+
+    server.await?;
+    assert!(eof.load(Ordering::SeqCst));
+
+    Ok(())
+}
@@ -0,0 +1,275 @@
+#![cfg(feature = "impl_tokio")]
+
+use core::time::Duration;
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::Arc,
+    time::Instant,
+};
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{
+    connector::Connector,
+    fbthrift_transport_response_handler::MockResponseHandler,
+    impl_tokio::{tcp_connector, TokioSleep, TokioTcpStream},
+    AsyncTransportConfiguration, HedgePolicy, HedgeTransport, Idempotency,
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpListener, TcpStream},
+};
+
+async fn echo(mut stream: TcpStream, first_delay: Duration) -> Result<(), IoError> {
+    let mut buf = vec![0; 5];
+    let mut delay = first_delay;
+    loop {
+        stream.read_exact(&mut buf).await?;
+        tokio::time::sleep(core::mem::take(&mut delay)).await;
+        stream.write_all(&buf).await?;
+    }
+}
+
+#[tokio::test]
+async fn hedge_slow_connection() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        // The first connection answers its first call slowly, the third replaces it.
+        let (stream_1, _) = listener.accept().await?;
+        tokio::spawn(echo(stream_1, Duration::from_millis(300)));
+        let (stream_2, _) = listener.accept().await?;
+        tokio::spawn(echo(stream_2, Duration::ZERO));
+        let (stream_3, _) = listener.accept().await?;
+        tokio::spawn(echo(stream_3, Duration::ZERO));
+
+        Result::<_, IoError>::Ok(())
+    });
+
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> =
+        vec![Arc::new(tcp_connector(addr)), Arc::new(tcp_connector(addr))];
+    let mut policy = HedgePolicy::new();
+    policy.set_max_delay(Duration::from_millis(50));
+    let mut idempotency = Idempotency::new();
+    idempotency.add_idempotent_fn("my_service", "get");
+    policy.set_idempotency(idempotency);
+    let transport = HedgeTransport::<_, TokioSleep, _>::new(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        policy,
+    )
+    .await?;
+    assert_eq!(transport.connections_len(), 2);
+    assert_eq!(transport.hedge_delay(), Duration::from_millis(50));
+
+    let now = Instant::now();
+    let out = transport
+        .call(
+            c"my_service",
+            c"get",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("abcde"));
+    assert!(now.elapsed() < Duration::from_millis(300));
+
+    // The loser was dropped, the slow connection reconnects before it is reused.
+    assert_eq!(transport.connections_stats()[0].in_flight, 0);
+    let (out_1, out_2) = tokio::join!(
+        transport.call(
+            c"my_service",
+            c"get",
+            Bytes::from("fghij"),
+            Default::default()
+        ),
+        transport.call(
+            c"my_service",
+            c"get",
+            Bytes::from("klmno"),
+            Default::default()
+        ),
+    );
+    assert_eq!(out_1?.into_inner(), Bytes::from("fghij"));
+    assert_eq!(out_2?.into_inner(), Bytes::from("klmno"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_after_hedge() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        let (stream_1, _) = listener.accept().await?;
+        tokio::spawn(echo(stream_1, Duration::from_secs(5)));
+        let (stream_2, _) = listener.accept().await?;
+        tokio::spawn(echo(stream_2, Duration::ZERO));
+
+        Result::<_, IoError>::Ok(())
+    });
+
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> =
+        vec![Arc::new(tcp_connector(addr)), Arc::new(tcp_connector(addr))];
+    let mut policy = HedgePolicy::new();
+    policy.set_max_delay(Duration::from_millis(50));
+    let mut idempotency = Idempotency::new();
+    idempotency.add_idempotent_fn("my_service", "get");
+    policy.set_idempotency(idempotency);
+    let transport = HedgeTransport::<_, TokioSleep, _>::new(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        policy,
+    )
+    .await?;
+
+    let out = transport
+        .call(
+            c"my_service",
+            c"get",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("abcde"));
+
+    // Nothing waits for the loser.
+    let now = Instant::now();
+    transport.close(Duration::from_secs(1)).await?;
+    assert!(now.elapsed() < Duration::from_millis(500));
+    assert!(transport
+        .connections_stats()
+        .iter()
+        .all(|stats| stats.in_flight == 0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_hedge_non_idempotent_fn() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        let (stream_1, _) = listener.accept().await?;
+        tokio::spawn(echo(stream_1, Duration::from_millis(200)));
+        let (stream_2, _) = listener.accept().await?;
+        tokio::spawn(echo(stream_2, Duration::ZERO));
+
+        Result::<_, IoError>::Ok(())
+    });
+
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> =
+        vec![Arc::new(tcp_connector(addr)), Arc::new(tcp_connector(addr))];
+    let mut policy = HedgePolicy::new();
+    policy.set_max_delay(Duration::from_millis(50));
+    let transport = HedgeTransport::<_, TokioSleep, _>::new(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        policy,
+    )
+    .await?;
+
+    let now = Instant::now();
+    let out = transport
+        .call(
+            c"my_service",
+            c"set",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("abcde"));
+    assert!(now.elapsed() >= Duration::from_millis(200));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn new_with_one_connector() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> = vec![Arc::new(tcp_connector(addr))];
+    let ret = HedgeTransport::<_, TokioSleep, _>::new(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        HedgePolicy::new(),
+    )
+    .await;
+    assert_eq!(
+        ret.err().map(|err| err.kind()),
+        Some(IoErrorKind::InvalidInput)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn latency_without_connection_wait() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            tokio::spawn(async move {
+                let mut buf = vec![0; 5];
+                loop {
+                    stream.read_exact(&mut buf).await?;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    stream.write_all(&buf).await?;
+                }
+                #[allow(unreachable_code)]
+                Result::<_, IoError>::Ok(())
+            });
+        }
+        #[allow(unreachable_code)]
+        Result::<_, IoError>::Ok(())
+    });
+
+    let connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> =
+        vec![Arc::new(tcp_connector(addr)), Arc::new(tcp_connector(addr))];
+    let mut policy = HedgePolicy::new();
+    policy.set_min_delay(Duration::ZERO);
+    policy.set_min_samples(1);
+    policy.set_percentile(1.0);
+    let transport = HedgeTransport::<_, TokioSleep, _>::new(
+        connectors,
+        AsyncTransportConfiguration::new(MockResponseHandler),
+        policy,
+    )
+    .await?;
+
+    // The third call waits for a connection first.
+    let (out_1, out_2, out_3) = tokio::join!(
+        transport.call(
+            c"my_service",
+            c"set",
+            Bytes::from("abcde"),
+            Default::default()
+        ),
+        transport.call(
+            c"my_service",
+            c"set",
+            Bytes::from("fghij"),
+            Default::default()
+        ),
+        transport.call(
+            c"my_service",
+            c"set",
+            Bytes::from("klmno"),
+            Default::default()
+        ),
+    );
+    out_1?;
+    out_2?;
+    out_3?;
+
+    let delay = transport.hedge_delay();
+    assert!(delay >= Duration::from_millis(100) && delay < Duration::from_millis(180));
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+#![cfg(all(feature = "tower", feature = "impl_tokio"))]
+
+use std::io::Error as IoError;
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{
+    fbthrift_transport_response_handler::MockResponseHandler,
+    impl_tokio::TokioSleep,
+    impl_tower::{ServiceTransport, ThriftRequest, TransportService},
+    AsyncTransport, AsyncTransportConfiguration,
+};
+use futures_util::io::Cursor;
+use tower::Service as _;
+
+#[tokio::test]
+async fn transport_service_and_service_transport() -> Result<(), tower::BoxError> {
+    let transport = AsyncTransport::<_, TokioSleep, _>::new(
+        Cursor::new(b"xxx12".to_vec()),
+        AsyncTransportConfiguration::new(MockResponseHandler),
+    );
+
+    let mut service = TransportService::new(transport);
+    let out = service
+        .call(ThriftRequest {
+            service_name: c"my_service",
+            fn_name: c"my_fn",
+            req: Bytes::from("req"),
+            rpc_options: Default::default(),
+        })
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("12"));
+
+    //
+    let transport = ServiceTransport::new(service);
+    let out = transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("req"),
+            Default::default(),
+        )
+        .await;
+    let err = out.expect_err("");
+    assert!(err.downcast_ref::<IoError>().is_some());
+    assert_eq!(err.to_string(), "Reach max parse response bytes count");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn service_transport() -> Result<(), Box<dyn std::error::Error>> {
+    let transport = AsyncTransport::<_, TokioSleep, _>::new(
+        Cursor::new(b"xxx12".to_vec()),
+        AsyncTransportConfiguration::new(MockResponseHandler),
+    );
+
+    let transport = ServiceTransport::new(TransportService::new(transport));
+    let out = transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("req"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("12"));
+
+    Ok(())
+}
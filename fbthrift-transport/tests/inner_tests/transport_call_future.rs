@@ -1,6 +1,5 @@
 use super::{block_on, Sleep};
 
-use core::ffi::CStr;
 use std::{
     io::Error as IoError,
     sync::{Arc, Mutex},
@@ -48,8 +47,8 @@ fn call_with_static_res() -> Result<(), Box<dyn std::error::Error>> {
         let req = Bytes::from("static");
         let call = Call::<_, Sleep, _>::new(
             stream.clone(),
-            CStr::from_bytes_with_nul(b"my_service\0").expect(""),
-            CStr::from_bytes_with_nul(b"my_fn\0").expect(""),
+            c"my_service",
+            c"my_fn",
             req,
             Default::default(),
             c.clone(),
@@ -105,8 +104,8 @@ fn call_with_dynamic_res() -> Result<(), Box<dyn std::error::Error>> {
         let req = Bytes::from("dynamic");
         let call = Call::<_, Sleep, _>::new(
             stream.clone(),
-            CStr::from_bytes_with_nul(b"my_service\0").expect(""),
-            CStr::from_bytes_with_nul(b"my_fn\0").expect(""),
+            c"my_service",
+            c"my_fn",
             req,
             Default::default(),
             c.clone(),
@@ -164,8 +163,8 @@ fn call_with_dynamic_res_and_less_buf_size() -> Result<(), Box<dyn std::error::E
         let req = Bytes::from("dynamic");
         let call = Call::<_, Sleep, _>::new(
             stream.clone(),
-            CStr::from_bytes_with_nul(b"my_service\0").expect(""),
-            CStr::from_bytes_with_nul(b"my_fn\0").expect(""),
+            c"my_service",
+            c"my_fn",
             req,
             Default::default(),
             c.clone(),
@@ -223,8 +222,8 @@ fn call_with_dynamic_res_and_less_max_buf_size() -> Result<(), Box<dyn std::erro
         let req = Bytes::from("dynamic");
         let call = Call::<_, Sleep, _>::new(
             stream.clone(),
-            CStr::from_bytes_with_nul(b"my_service\0").expect(""),
-            CStr::from_bytes_with_nul(b"my_fn\0").expect(""),
+            c"my_service",
+            c"my_fn",
             req,
             Default::default(),
             c.clone(),
@@ -243,6 +242,292 @@ fn call_with_dynamic_res_and_less_max_buf_size() -> Result<(), Box<dyn std::erro
     })
 }
 
+#[test]
+fn call_with_dynamic_res_and_growing_buf() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Clone, Default)]
+    pub struct FooResponseHandler {
+        parsed_lens: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl ResponseHandler for FooResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            self.parsed_lens
+                .lock()
+                .expect("")
+                .push(response_bytes.len());
+            Ok((response_bytes.len() == 30).then_some(30))
+        }
+    }
+
+    block_on(async {
+        let mut buf = [&b"dynamic"[..], &[b'x'; 30][..]].concat();
+        let cursor = Cursor::new(&mut buf);
+        let stream = Arc::new(Mutex::new(cursor));
+        let h = FooResponseHandler::default();
+        let mut c = AsyncTransportConfiguration::new(h.clone());
+        c.set_buf_size(2);
+        c.set_max_buf_size(64);
+        c.set_max_parse_response_bytes_count(99);
+
+        //
+        let req = Bytes::from("dynamic");
+        let call = Call::<_, Sleep, _>::new(
+            stream.clone(),
+            c"my_service",
+            c"my_fn",
+            req,
+            Default::default(),
+            c.clone(),
+        );
+
+        let out = call.await.expect("");
+        assert_eq!(out.into_inner(), Bytes::from(vec![b'x'; 30]));
+
+        assert_eq!(*h.parsed_lens.lock().expect(""), vec![2, 4, 8, 16, 30]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_pending_reads() -> Result<(), Box<dyn std::error::Error>> {
+    use core::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures_util::io::{AsyncRead, AsyncWrite};
+
+    // Pending before every read, which returns at most 2 bytes.
+    struct FooStream {
+        res: Vec<u8>,
+        pending: bool,
+    }
+
+    impl AsyncRead for FooStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize, IoError>> {
+            let this = self.get_mut();
+            this.pending = !this.pending;
+            if this.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = buf.len().min(this.res.len()).min(2);
+            buf[..n].copy_from_slice(&this.res[..n]);
+            this.res.drain(..n);
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    impl AsyncWrite for FooStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize, IoError>> {
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct FooResponseHandler;
+
+    impl ResponseHandler for FooResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            Ok((response_bytes.len() >= 5).then_some(5))
+        }
+    }
+
+    block_on(async {
+        let stream = Arc::new(Mutex::new(FooStream {
+            res: b"abcdefg".to_vec(),
+            pending: false,
+        }));
+        let mut c = AsyncTransportConfiguration::new(FooResponseHandler);
+        c.set_max_parse_response_bytes_count(99);
+
+        let call = Call::<_, Sleep, _>::new(
+            stream.clone(),
+            c"my_service",
+            c"my_fn",
+            Bytes::from("req"),
+            Default::default(),
+            c.clone(),
+        );
+
+        let out = call.await.expect("");
+        assert_eq!(out.into_inner(), Bytes::from("abcde"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_incremental_parse() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Clone, Default)]
+    pub struct FooResponseHandler {
+        scanned: usize,
+        reset_count: Arc<Mutex<usize>>,
+        new_bytes_froms: Arc<Mutex<Vec<(usize, usize)>>>,
+    }
+
+    impl ResponseHandler for FooResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            _response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            unimplemented!()
+        }
+
+        fn reset(&mut self) {
+            self.scanned = 0;
+            *self.reset_count.lock().expect("") += 1;
+        }
+
+        fn parse_new_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+            new_bytes_from: usize,
+        ) -> Result<Option<usize>, IoError> {
+            assert_eq!(new_bytes_from, self.scanned);
+            self.new_bytes_froms
+                .lock()
+                .expect("")
+                .push((response_bytes.len(), new_bytes_from));
+
+            // Scans for the terminator only in what is new.
+            let n = response_bytes[new_bytes_from..]
+                .iter()
+                .position(|b| *b == b';')
+                .map(|i| new_bytes_from + i + 1);
+            self.scanned = response_bytes.len();
+            Ok(n)
+        }
+    }
+
+    block_on(async {
+        let h = FooResponseHandler::default();
+        let mut c = AsyncTransportConfiguration::new(h.clone());
+        c.set_buf_size(4);
+        c.set_max_parse_response_bytes_count(99);
+
+        for _ in 0..2 {
+            let mut buf = b"dynamicfoobarbaz;".to_vec();
+            let cursor = Cursor::new(&mut buf);
+            let stream = Arc::new(Mutex::new(cursor));
+
+            let req = Bytes::from("dynamic");
+            let call = Call::<_, Sleep, _>::new(
+                stream.clone(),
+                c"my_service",
+                c"my_fn",
+                req,
+                Default::default(),
+                c.clone(),
+            );
+
+            let out = call.await.expect("");
+            assert_eq!(out.into_inner(), Bytes::from("foobarbaz;"));
+        }
+
+        assert_eq!(*h.reset_count.lock().expect(""), 2);
+        assert_eq!(
+            *h.new_bytes_froms.lock().expect(""),
+            vec![(4, 0), (8, 4), (10, 8), (4, 0), (8, 4), (10, 8)]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_buffer_pool() -> Result<(), Box<dyn std::error::Error>> {
+    use fbthrift_transport::{buffer_pool::BufferPoolStats, BufferPool};
+    use fbthrift_transport_response_handler::MockResponseHandler;
+
+    block_on(async {
+        let pool = Arc::new(BufferPool::default());
+        let mut c = AsyncTransportConfiguration::new(MockResponseHandler);
+        c.set_buffer_pool(pool.clone());
+
+        for _ in 0..2 {
+            let mut buf = b"dynamicfoo".to_vec();
+            let cursor = Cursor::new(&mut buf);
+            let stream = Arc::new(Mutex::new(cursor));
+
+            let req = Bytes::from("dynamic");
+            let call = Call::<_, Sleep, _>::new(
+                stream.clone(),
+                c"my_service",
+                c"my_fn",
+                req,
+                Default::default(),
+                c.clone(),
+            );
+
+            let out = call.await.expect("");
+            assert_eq!(out.into_inner(), Bytes::from("foo"));
+        }
+
+        assert_eq!(
+            pool.stats(),
+            BufferPoolStats {
+                hits: 1,
+                misses: 1,
+                recycled: 2,
+                discarded: 0
+            }
+        );
+
+        Ok(())
+    })
+}
+
 #[test]
 fn call_with_dynamic_res_and_too_many_read() -> Result<(), Box<dyn std::error::Error>> {
     #[derive(Clone)]
@@ -284,8 +569,8 @@ fn call_with_dynamic_res_and_too_many_read() -> Result<(), Box<dyn std::error::E
         let req = Bytes::from("dynamic");
         let call = Call::<_, Sleep, _>::new(
             stream.clone(),
-            CStr::from_bytes_with_nul(b"my_service\0").expect(""),
-            CStr::from_bytes_with_nul(b"my_fn\0").expect(""),
+            c"my_service",
+            c"my_fn",
             req,
             Default::default(),
             c.clone(),
@@ -303,3 +588,483 @@ fn call_with_dynamic_res_and_too_many_read() -> Result<(), Box<dyn std::error::E
         Ok(())
     })
 }
+
+#[test]
+fn call_with_response_handler_v2() -> Result<(), Box<dyn std::error::Error>> {
+    use core::task::{Context, Poll};
+
+    use fbthrift_transport_response_handler::{
+        CallContext, ResponseHandlerV2, ResponseParseOutcome,
+    };
+
+    #[derive(Clone, Default)]
+    pub struct FooResponseHandler {
+        static_polled: bool,
+        parsed_lens: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl ResponseHandlerV2 for FooResponseHandler {
+        fn poll_static_response_bytes(
+            &mut self,
+            cx: &mut Context<'_>,
+            ctx: &CallContext<'_>,
+        ) -> Poll<Result<Option<Vec<u8>>, IoError>> {
+            assert_eq!(ctx.fn_name, b"my_fn");
+            assert_eq!(ctx.request_bytes, b"dynamic");
+            assert_eq!(ctx.seq_id, 7);
+
+            // Not ready on the first poll.
+            if !self.static_polled {
+                self.static_polled = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(Ok(None))
+        }
+
+        fn poll_parse_response_bytes(
+            &mut self,
+            _cx: &mut Context<'_>,
+            _ctx: &CallContext<'_>,
+            response_bytes: &[u8],
+            _new_bytes_from: usize,
+        ) -> Poll<Result<ResponseParseOutcome, IoError>> {
+            self.parsed_lens
+                .lock()
+                .expect("")
+                .push(response_bytes.len());
+            Poll::Ready(Ok(match response_bytes.len() {
+                1 => ResponseParseOutcome::NeedMore(10),
+                11 => ResponseParseOutcome::Rewrite {
+                    len: 11,
+                    payload: response_bytes[..5].to_ascii_uppercase(),
+                },
+                _ => unimplemented!(),
+            }))
+        }
+    }
+
+    block_on(async {
+        let mut buf = b"dynamichello world".to_vec();
+        let cursor = Cursor::new(&mut buf);
+        let stream = Arc::new(Mutex::new(cursor));
+        let h = FooResponseHandler::default();
+        let mut c = AsyncTransportConfiguration::new(h.clone());
+        c.set_buf_size(1);
+
+        //
+        let req = Bytes::from("dynamic");
+        let call = Call::<_, Sleep, _>::new(
+            stream.clone(),
+            c"my_service",
+            c"my_fn",
+            req,
+            Default::default(),
+            c.clone(),
+        )
+        .with_seq_id(7);
+
+        let out = call.await.expect("");
+        assert_eq!(out.into_inner(), Bytes::from("HELLO"));
+
+        assert_eq!(*h.parsed_lens.lock().expect(""), vec![1, 11]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_response_handler_v2_and_huge_need_more() -> Result<(), Box<dyn std::error::Error>> {
+    use core::task::{Context, Poll};
+
+    use fbthrift_transport_response_handler::{
+        CallContext, ResponseHandlerV2, ResponseParseOutcome,
+    };
+
+    #[derive(Clone)]
+    pub struct FooResponseHandler;
+
+    impl ResponseHandlerV2 for FooResponseHandler {
+        fn poll_static_response_bytes(
+            &mut self,
+            _cx: &mut Context<'_>,
+            _ctx: &CallContext<'_>,
+        ) -> Poll<Result<Option<Vec<u8>>, IoError>> {
+            Poll::Ready(Ok(None))
+        }
+
+        fn poll_parse_response_bytes(
+            &mut self,
+            _cx: &mut Context<'_>,
+            _ctx: &CallContext<'_>,
+            _response_bytes: &[u8],
+            _new_bytes_from: usize,
+        ) -> Poll<Result<ResponseParseOutcome, IoError>> {
+            Poll::Ready(Ok(ResponseParseOutcome::NeedMore(usize::MAX)))
+        }
+    }
+
+    block_on(async {
+        let mut buf = b"dynamichello".to_vec();
+        let cursor = Cursor::new(&mut buf);
+        let stream = Arc::new(Mutex::new(cursor));
+        let c = AsyncTransportConfiguration::new(FooResponseHandler);
+
+        let call = Call::<_, Sleep, _>::new(
+            stream.clone(),
+            c"my_service",
+            c"my_fn",
+            Bytes::from("dynamic"),
+            Default::default(),
+            c.clone(),
+        );
+
+        let err = call.await.expect_err("");
+        assert_eq!(err.to_string(), "Reach max buffer size");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_call_observer() -> Result<(), Box<dyn std::error::Error>> {
+    use core::{ffi::CStr, time::Duration};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use fbthrift_transport::{CallMetrics, CallObserver, CallOutcome};
+    use fbthrift_transport_response_handler::MockResponseHandler;
+
+    #[derive(Default)]
+    struct FooCallObserver {
+        write_done_count: AtomicUsize,
+        first_byte_count: AtomicUsize,
+        completed: Mutex<Vec<CallMetrics>>,
+    }
+
+    impl CallObserver for FooCallObserver {
+        fn on_write_done(
+            &self,
+            _service_name: &'static CStr,
+            _fn_name: &'static CStr,
+            request_size: usize,
+            _elapsed: Duration,
+        ) {
+            assert_eq!(request_size, 7);
+            self.write_done_count.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_first_byte(
+            &self,
+            _service_name: &'static CStr,
+            _fn_name: &'static CStr,
+            _elapsed: Duration,
+        ) {
+            self.first_byte_count.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_complete(&self, metrics: &CallMetrics) {
+            self.completed.lock().expect("").push(metrics.clone());
+        }
+    }
+
+    block_on(async {
+        let mut buf = b"123456789012".to_vec();
+        let cursor = Cursor::new(&mut buf);
+        let stream = Arc::new(Mutex::new(cursor));
+        let observer = Arc::new(FooCallObserver::default());
+        let mut c = AsyncTransportConfiguration::new(MockResponseHandler);
+        c.set_call_observer(observer.clone());
+
+        //
+        let req = Bytes::from("dynamic");
+        let call = Call::<_, Sleep, _>::new(
+            stream.clone(),
+            c"my_service",
+            c"my_fn",
+            req,
+            fbthrift_transport::transport::AsyncTransportRpcOptions {
+                retry_count: 2,
+                ..Default::default()
+            },
+            c.clone(),
+        );
+
+        let out = call.await.expect("");
+        assert_eq!(out.into_inner(), Bytes::from("89012"));
+
+        assert_eq!(observer.write_done_count.load(Ordering::SeqCst), 1);
+        assert_eq!(observer.first_byte_count.load(Ordering::SeqCst), 1);
+        let completed = observer.completed.lock().expect("");
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].service_name, c"my_service");
+        assert_eq!(completed[0].fn_name, c"my_fn");
+        assert_eq!(completed[0].request_size, 7);
+        assert_eq!(completed[0].response_size, 5);
+        assert_eq!(completed[0].outcome, CallOutcome::Success);
+        assert_eq!(completed[0].retry_count, 2);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_wire_dump() -> Result<(), Box<dyn std::error::Error>> {
+    use fbthrift_transport::{
+        wire_dump::{WireDumpContext, WireDumpKind, WireDumpWriter},
+        WireDump,
+    };
+
+    #[derive(Clone)]
+    pub struct FooResponseHandler;
+
+    impl ResponseHandler for FooResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            _response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            Ok(Some(2))
+        }
+    }
+
+    #[derive(Default)]
+    struct FooWireDumpWriter(Mutex<Vec<(WireDumpKind, usize, usize)>>);
+
+    impl WireDumpWriter for FooWireDumpWriter {
+        fn write(&self, ctx: &WireDumpContext, _dump: &str) {
+            self.0
+                .lock()
+                .expect("")
+                .push((ctx.kind, ctx.offset, ctx.len));
+        }
+    }
+
+    block_on(async {
+        let mut buf = b"123456789012".to_vec();
+        let cursor = Cursor::new(&mut buf);
+        let stream = Arc::new(Mutex::new(cursor));
+        let writer = Arc::new(FooWireDumpWriter::default());
+        let mut c = AsyncTransportConfiguration::new(FooResponseHandler);
+        c.set_wire_dump(WireDump::new(writer.clone()));
+
+        //
+        let req = Bytes::from("dynamic");
+        let call = Call::<_, Sleep, _>::new(
+            stream.clone(),
+            c"my_service",
+            c"my_fn",
+            req,
+            Default::default(),
+            c.clone(),
+        );
+
+        let out = call.await.expect("");
+        assert_eq!(out.into_inner(), Bytes::from("89"));
+
+        assert_eq!(
+            *writer.0.lock().expect(""),
+            vec![
+                (WireDumpKind::Request, 0, 7),
+                (WireDumpKind::ParseAttempt, 0, 5),
+                (WireDumpKind::Discarded, 2, 3),
+            ]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_oneway() -> Result<(), Box<dyn std::error::Error>> {
+    use fbthrift::Transport as _;
+    use fbthrift_transport::AsyncTransport;
+    use fbthrift_transport_response_handler::MockResponseHandler;
+
+    block_on(async {
+        let stream = Cursor::new(b"onewaydynamicfoo".to_vec());
+        let transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(MockResponseHandler),
+        );
+
+        transport
+            .call_oneway(c"my_service", c"my_fn", Bytes::from("oneway"))
+            .await?;
+
+        // Nothing was read, so the next call gets its own response.
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("dynamic"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("foo"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_headers() -> Result<(), Box<dyn std::error::Error>> {
+    use fbthrift_transport::{transport::AsyncTransportRpcOptions, AsyncTransport};
+    use fbthrift_transport_response_handler::Headers;
+
+    // Headers go before the request as `k=v;`, the response ones are read back the same way.
+    #[derive(Clone)]
+    pub struct FooResponseHandler;
+
+    impl ResponseHandler for FooResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            Ok(Some(response_bytes.len()))
+        }
+
+        fn make_request_bytes_with_headers(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            request_bytes: &[u8],
+            headers: &Headers,
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            let mut bytes = vec![];
+            for (k, v) in headers {
+                bytes.extend_from_slice(format!("{k}={v};").as_bytes());
+            }
+            bytes.extend_from_slice(request_bytes);
+            Ok(Some(bytes))
+        }
+
+        fn parse_response_headers(&mut self, response_bytes: &[u8]) -> Result<Headers, IoError> {
+            Ok(String::from_utf8_lossy(response_bytes)
+                .split(';')
+                .filter_map(|kv| kv.split_once('='))
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect())
+        }
+    }
+
+    block_on(async {
+        let stream = Cursor::new(b"t=1;reqload=9;res".to_vec());
+        let transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(FooResponseHandler),
+        );
+
+        let rpc_options = AsyncTransportRpcOptions {
+            headers: Headers::from([("t".to_owned(), "1".to_owned())]),
+            ..Default::default()
+        };
+        let (out, headers) = transport
+            .call_with_response_headers(c"my_service", c"my_fn", Bytes::from("req"), rpc_options)
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("load=9;res"));
+        assert_eq!(
+            headers,
+            Headers::from([("load".to_owned(), "9".to_owned())])
+        );
+
+        //
+        let mut buf = b"reqload=9;res".to_vec();
+        let cursor = Cursor::new(&mut buf);
+        let stream = Arc::new(Mutex::new(cursor));
+        let mut call = Call::<_, Sleep, _>::new(
+            stream.clone(),
+            c"my_service",
+            c"my_fn",
+            Bytes::from("req"),
+            Default::default(),
+            AsyncTransportConfiguration::new(FooResponseHandler),
+        );
+        assert_eq!(call.get_response_headers(), None);
+
+        let out = (&mut call).await?;
+        assert_eq!(out.into_inner(), Bytes::from("load=9;res"));
+        assert_eq!(call.get_response_headers().map(|h| h.len()), Some(1));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_with_stats() -> Result<(), Box<dyn std::error::Error>> {
+    use fbthrift::Transport as _;
+    use fbthrift_transport::{AsyncTransport, TransportStats};
+    use fbthrift_transport_response_handler::MockResponseHandler;
+
+    block_on(async {
+        let stream = Cursor::new(b"reqfoo".to_vec());
+        let transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(MockResponseHandler),
+        );
+        assert_eq!(transport.stats(), TransportStats::default());
+
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("foo"));
+
+        // Nothing left to read.
+        assert!(transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("abc"),
+                Default::default(),
+            )
+            .await
+            .is_err());
+
+        let stats = transport.stats();
+        assert_eq!(
+            stats,
+            TransportStats {
+                calls_started: 2,
+                calls_succeeded: 1,
+                calls_failed_other: 1,
+                bytes_written: 6,
+                bytes_read: 3,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.calls_failed(), 1);
+
+        // Dropped while pending.
+        let call = transport.call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("def"),
+            Default::default(),
+        );
+        drop(call);
+        assert_eq!(transport.stats().in_flight, 0);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,93 @@
+use super::{block_on, Sleep};
+
+use core::ffi::CStr;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{AsyncTransport, AsyncTransportConfiguration, Interceptor};
+use fbthrift_transport_response_handler::MockResponseHandler;
+use futures_util::io::Cursor;
+
+struct FooInterceptor {
+    name: &'static str,
+    records: Arc<Mutex<Vec<String>>>,
+}
+
+impl Interceptor for FooInterceptor {
+    fn before_call(
+        &self,
+        service_name: &'static CStr,
+        fn_name: &'static CStr,
+        request_bytes: Bytes,
+    ) -> Result<Bytes, anyhow::Error> {
+        assert_eq!(service_name, c"my_service");
+        assert_eq!(fn_name, c"my_fn");
+        self.records
+            .lock()
+            .expect("")
+            .push(format!("{} before", self.name));
+
+        let mut request_bytes = request_bytes.to_vec();
+        request_bytes.extend_from_slice(self.name.as_bytes());
+        Ok(request_bytes.into())
+    }
+
+    fn after_call(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        request_bytes: &Bytes,
+        result: Result<Bytes, anyhow::Error>,
+    ) -> Result<Bytes, anyhow::Error> {
+        assert_eq!(request_bytes, &Bytes::from("reqab"));
+        self.records
+            .lock()
+            .expect("")
+            .push(format!("{} after", self.name));
+
+        let res = result?;
+        if res.len() > 2 {
+            anyhow::bail!("too long");
+        }
+        Ok(res)
+    }
+}
+
+#[test]
+fn call_with_interceptors() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let records = Arc::new(Mutex::new(vec![]));
+
+        let stream = Cursor::new(b"xxxxx12".to_vec());
+        let mut transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(MockResponseHandler),
+        );
+        transport.add_interceptor(Arc::new(FooInterceptor {
+            name: "a",
+            records: records.clone(),
+        }));
+        transport.add_interceptor(Arc::new(FooInterceptor {
+            name: "b",
+            records: records.clone(),
+        }));
+
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("12"));
+
+        assert_eq!(
+            *records.lock().expect(""),
+            vec!["a before", "b before", "b after", "a after"]
+        );
+
+        Ok(())
+    })
+}
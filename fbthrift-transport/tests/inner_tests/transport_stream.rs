@@ -0,0 +1,504 @@
+use super::{block_on, Sleep};
+
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    collections::VecDeque,
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use fbthrift::{ClientStreamElement, Transport as _};
+use fbthrift_transport::{AsyncTransport, AsyncTransportConfiguration};
+use fbthrift_transport_response_handler::{
+    CallContext, ResponseHandlerV2, ResponseParseOutcome, StreamElementKind,
+};
+use futures_util::{
+    io::{AsyncRead, AsyncWrite},
+    SinkExt as _, StreamExt as _,
+};
+
+// Reads at most one chunk at a time, records what is written.
+struct Duplex {
+    chunks: VecDeque<Vec<u8>>,
+    written: Arc<Mutex<Vec<u8>>>,
+    // Only `max_written` bytes are accepted, later writes stay pending.
+    max_written: usize,
+}
+
+impl Duplex {
+    fn new(chunks: &[&[u8]]) -> (Self, Arc<Mutex<Vec<u8>>>) {
+        let written = Arc::new(Mutex::new(vec![]));
+        (
+            Self {
+                chunks: chunks.iter().map(|chunk| chunk.to_vec()).collect(),
+                written: written.clone(),
+                max_written: usize::MAX,
+            },
+            written,
+        )
+    }
+}
+
+impl AsyncRead for Duplex {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let chunks = &mut self.get_mut().chunks;
+        let chunk = match chunks.front_mut() {
+            Some(chunk) => chunk,
+            None => return Poll::Ready(Ok(0)),
+        };
+        let n = buf.len().min(chunk.len());
+        buf[..n].copy_from_slice(&chunk[..n]);
+        chunk.drain(..n);
+        if chunk.is_empty() {
+            chunks.pop_front();
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for Duplex {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let mut written = self.written.lock().expect("");
+        let n = buf.len().min(self.max_written - written.len());
+        if n == 0 {
+            return Poll::Pending;
+        }
+        written.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Elements end with `;`, the stream ends with `end;`.
+#[derive(Clone)]
+struct FooResponseHandler;
+
+impl ResponseHandlerV2 for FooResponseHandler {
+    fn poll_static_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+    ) -> Poll<Result<Option<Vec<u8>>, IoError>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn poll_parse_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+        response_bytes: &[u8],
+        _new_bytes_from: usize,
+    ) -> Poll<Result<ResponseParseOutcome, IoError>> {
+        Poll::Ready(Ok(match response_bytes.iter().position(|b| *b == b';') {
+            Some(i) => ResponseParseOutcome::Complete(i + 1),
+            None => ResponseParseOutcome::NeedMore(1),
+        }))
+    }
+
+    fn stream_element_kind(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        element_bytes: &[u8],
+    ) -> Result<StreamElementKind, IoError> {
+        Ok(match element_bytes {
+            b"end;" => StreamElementKind::End,
+            b"ex;" => StreamElementKind::ApplicationEx,
+            _ => StreamElementKind::Reply,
+        })
+    }
+
+    fn make_stream_credits_bytes(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        credits: u32,
+    ) -> Option<Vec<u8>> {
+        Some(format!("C{credits};").into_bytes())
+    }
+
+    fn make_stream_cancel_bytes(&mut self, _ctx: &CallContext<'_>) -> Option<Vec<u8>> {
+        Some(b"X;".to_vec())
+    }
+
+    fn make_sink_complete_bytes(&mut self, _ctx: &CallContext<'_>) -> Option<Vec<u8>> {
+        Some(b"done;".to_vec())
+    }
+}
+
+#[test]
+fn call_stream() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let (stream, written) = Duplex::new(&[b"init;a;ex;b;end;", b"next;"]);
+        let mut c = AsyncTransportConfiguration::new(FooResponseHandler);
+        c.set_stream_credits(2);
+        let transport = AsyncTransport::<_, Sleep, _>::new(stream, c);
+
+        let (res, mut stream) = transport
+            .call_stream(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(res.into_inner(), Bytes::from("init;"));
+
+        let mut elements = vec![];
+        while let Some(element) = stream.next().await {
+            elements.push(match element? {
+                ClientStreamElement::Reply(cursor) => ("reply", cursor.into_inner()),
+                ClientStreamElement::ApplicationEx(cursor) => ("ex", cursor.into_inner()),
+            });
+        }
+        assert_eq!(
+            elements,
+            vec![
+                ("reply", Bytes::from("a;")),
+                ("ex", Bytes::from("ex;")),
+                ("reply", Bytes::from("b;"))
+            ]
+        );
+        drop(stream);
+
+        assert_eq!(&written.lock().expect("")[..], b"req;C2;C2;");
+        assert!(!transport.is_desynced());
+
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("next;"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_stream_cancel() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let (stream, written) = Duplex::new(&[b"init;a;b;end;", b"next;"]);
+        let transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(FooResponseHandler),
+        );
+
+        let (_, mut stream) = transport
+            .open_stream(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert!(stream.next().await.is_some());
+        stream.cancel().await?;
+
+        assert_eq!(&written.lock().expect("")[..], b"req;X;");
+        assert!(!transport.is_desynced());
+
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("next;"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_stream_drop() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let (stream, _) = Duplex::new(&[b"init;a;b;end;", b"next;"]);
+        let transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(FooResponseHandler),
+        );
+
+        let (_, mut stream) = transport
+            .open_stream(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert!(stream.next().await.is_some());
+        drop(stream);
+
+        assert!(transport.is_desynced());
+
+        // Without a connector there is nothing to reconnect with.
+        let err = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("desynchronized"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_stream_busy() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let (stream, written) = Duplex::new(&[b"init;a;end;", b"next;"]);
+        let transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(FooResponseHandler),
+        );
+
+        let (_, mut stream) = transport
+            .open_stream(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+
+        // Nothing is written while the stream is open.
+        let err = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("other;"),
+                Default::default(),
+            )
+            .await
+            .expect_err("");
+        assert_eq!(err.to_string(), "Connection busy with an open stream");
+        assert_eq!(&written.lock().expect("")[..], b"req;");
+
+        while let Some(element) = stream.next().await {
+            element?;
+        }
+
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("next;"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_sink() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let (stream, written) = Duplex::new(&[b"init;", b"final;", b"next;"]);
+        let transport = AsyncTransport::<_, Sleep, _>::new(
+            stream,
+            AsyncTransportConfiguration::new(FooResponseHandler),
+        );
+
+        let (res, mut sink) = transport
+            .open_sink(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(res.into_inner(), Bytes::from("init;"));
+
+        sink.send(Bytes::from("a;")).await?;
+        sink.send(Bytes::from("b;")).await?;
+        let res = sink.finish().await?;
+        assert_eq!(res.into_inner(), Bytes::from("final;"));
+
+        assert_eq!(&written.lock().expect("")[..], b"req;a;b;done;");
+        assert!(!transport.is_desynced());
+
+        let out = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("next;"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_sink_routed() -> Result<(), Box<dyn std::error::Error>> {
+    use fbthrift_transport_response_handler::{ResponseHandler, Router};
+
+    #[derive(Clone)]
+    struct DelimitedResponseHandler;
+
+    impl ResponseHandler for DelimitedResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            Ok(response_bytes
+                .iter()
+                .position(|b| *b == b';')
+                .map(|i| i + 1))
+        }
+    }
+
+    #[derive(Clone)]
+    struct FixedResponseHandler;
+
+    impl ResponseHandler for FixedResponseHandler {
+        fn try_make_static_response_bytes(
+            &mut self,
+            _service_name: &'static [u8],
+            _fn_name: &'static [u8],
+            _request_bytes: &[u8],
+        ) -> Result<Option<Vec<u8>>, IoError> {
+            Ok(None)
+        }
+
+        fn parse_response_bytes(
+            &mut self,
+            response_bytes: &[u8],
+        ) -> Result<Option<usize>, IoError> {
+            Ok((response_bytes.len() >= 2).then_some(2))
+        }
+    }
+
+    block_on(async {
+        let (stream, written) = Duplex::new(&[b"init;", b"final-response;"]);
+        let router = Router::new(FixedResponseHandler).route(
+            "my_service",
+            "my_fn",
+            DelimitedResponseHandler,
+        );
+        let transport =
+            AsyncTransport::<_, Sleep, _>::new(stream, AsyncTransportConfiguration::new(router));
+
+        let (res, mut sink) = transport
+            .open_sink(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(res.into_inner(), Bytes::from("init;"));
+
+        // Every element is parsed by the route of the call.
+        sink.send(Bytes::from("a;")).await?;
+        let res = sink.finish().await?;
+        assert_eq!(res.into_inner(), Bytes::from("final-response;"));
+
+        assert_eq!(&written.lock().expect("")[..], b"req;a;");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_sink_write_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let (mut stream, written) = Duplex::new(&[b"init;"]);
+        stream.max_written = 6;
+        let mut c = AsyncTransportConfiguration::new(FooResponseHandler);
+        c.set_write_timeout(Duration::from_millis(100));
+        let transport = AsyncTransport::<_, Sleep, _>::new(stream, c);
+
+        let (_, mut sink) = transport
+            .open_sink(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await?;
+
+        let err = sink.send(Bytes::from("abc;")).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<IoError>().map(|err| err.kind()),
+            Some(IoErrorKind::TimedOut)
+        );
+        assert_eq!(&written.lock().expect("")[..], b"req;ab");
+
+        assert!(sink.send(Bytes::from("d;")).await.is_err());
+        drop(sink);
+        assert!(transport.is_desynced());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn call_write_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    block_on(async {
+        let (mut stream, written) = Duplex::new(&[b"res;"]);
+        stream.max_written = 2;
+        let mut c = AsyncTransportConfiguration::new(FooResponseHandler);
+        c.set_write_timeout(Duration::from_millis(100));
+        let transport = AsyncTransport::<_, Sleep, _>::new(stream, c);
+
+        let err = transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("req;"),
+                Default::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<IoError>().map(|err| err.kind()),
+            Some(IoErrorKind::TimedOut)
+        );
+        assert_eq!(&written.lock().expect("")[..], b"re");
+        assert_eq!(transport.stats().timeouts, 1);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,306 @@
+#![cfg(feature = "impl_tokio")]
+
+use core::{
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{
+    connector::Connector,
+    fbthrift_transport_response_handler::{CallContext, ResponseHandlerV2, ResponseParseOutcome},
+    impl_tokio::{tcp_connector, TokioSleep, TokioTcpStream},
+    AsyncTransport, AsyncTransportConfiguration, BalanceStrategy, BalancedTransport,
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+#[derive(Clone)]
+struct FooResponseHandler;
+
+impl ResponseHandlerV2 for FooResponseHandler {
+    fn poll_static_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+    ) -> Poll<Result<Option<Vec<u8>>, IoError>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn poll_parse_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+        response_bytes: &[u8],
+        _new_bytes_from: usize,
+    ) -> Poll<Result<ResponseParseOutcome, IoError>> {
+        Poll::Ready(Ok(match response_bytes.len() {
+            n if n >= 5 => ResponseParseOutcome::Complete(5),
+            n => ResponseParseOutcome::NeedMore(5 - n),
+        }))
+    }
+
+    fn make_interaction_terminate_bytes(&mut self, interaction_id: i64) -> Option<Vec<u8>> {
+        Some(format!("T{interaction_id:04}").into_bytes())
+    }
+}
+
+// Echoes 5 bytes frames, except the terminate ones, records all of them.
+fn server(
+    listener: TcpListener,
+    frames: Arc<Mutex<Vec<Bytes>>>,
+) -> JoinHandle<Result<(), IoError>> {
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            let frames = frames.clone();
+            tokio::spawn(async move {
+                let mut buf = vec![0; 5];
+                loop {
+                    stream.read_exact(&mut buf).await?;
+                    frames.lock().expect("").push(Bytes::from(buf.clone()));
+                    if buf[0] != b'T' {
+                        stream.write_all(&buf).await?;
+                    }
+                }
+                #[allow(unreachable_code)]
+                Result::<_, IoError>::Ok(())
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn create_interaction() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let frames = Arc::new(Mutex::new(vec![]));
+    let server = server(listener, frames.clone());
+
+    let transport = AsyncTransport::with_tokio_tcp_connect(
+        addr,
+        AsyncTransportConfiguration::new(FooResponseHandler),
+    )
+    .await?;
+    assert_eq!(transport.get_interaction_id(), None);
+
+    let interaction_1 = transport.create_interaction(c"MyInteraction")?;
+    let interaction_2 = transport.create_interaction(c"MyInteraction")?;
+    assert_eq!(interaction_1.get_interaction_id(), Some(1));
+    assert_eq!(
+        interaction_1.get_interaction_method_name(),
+        Some(c"MyInteraction")
+    );
+    assert_eq!(interaction_2.get_interaction_id(), Some(2));
+    assert!(interaction_1.create_interaction(c"MyInteraction").is_err());
+
+    let out = interaction_1
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("abcde"));
+
+    drop(interaction_1);
+
+    // The terminate frame goes before the next call on the connection.
+    let out = transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("fghij"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("fghij"));
+
+    assert_eq!(
+        *frames.lock().expect(""),
+        vec![
+            Bytes::from("abcde"),
+            Bytes::from("T0001"),
+            Bytes::from("fghij")
+        ]
+    );
+
+    drop(interaction_2);
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_interaction_on_balanced_transport() -> Result<(), Box<dyn std::error::Error>> {
+    let mut connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> = vec![];
+    let mut servers = vec![];
+    let mut frames = vec![];
+    for _ in 0..2 {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        connectors.push(Arc::new(tcp_connector(listener.local_addr()?)));
+        let server_frames = Arc::new(Mutex::new(vec![]));
+        servers.push(server(listener, server_frames.clone()));
+        frames.push(server_frames);
+    }
+
+    let transport = BalancedTransport::<_, TokioSleep, _>::new(
+        connectors,
+        AsyncTransportConfiguration::new(FooResponseHandler),
+        BalanceStrategy::RoundRobin,
+    );
+
+    // Bound on its first call, nothing is connected yet.
+    let interaction = transport.create_interaction(c"MyInteraction")?;
+    assert!(interaction.create_interaction(c"MyInteraction").is_err());
+    for _ in 0..3 {
+        let out = interaction
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("abcde"),
+                Default::default(),
+            )
+            .await?;
+        assert_eq!(out.into_inner(), Bytes::from("abcde"));
+    }
+
+    // The second endpoint, then the connection of the interaction.
+    for _ in 0..2 {
+        transport
+            .call(
+                c"my_service",
+                c"my_fn",
+                Bytes::from("fghij"),
+                Default::default(),
+            )
+            .await?;
+    }
+
+    assert_eq!(frames[0].lock().expect("").len(), 4);
+    assert_eq!(frames[1].lock().expect("").len(), 1);
+
+    for server in servers {
+        server.abort();
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_interaction_call_on_balanced_transport() -> Result<(), Box<dyn std::error::Error>> {
+    let mut connectors: Vec<Arc<dyn Connector<TokioTcpStream>>> = vec![];
+    let mut servers = vec![];
+    for _ in 0..2 {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        connectors.push(Arc::new(tcp_connector(listener.local_addr()?)));
+        servers.push(server(listener, Arc::new(Mutex::new(vec![]))));
+    }
+
+    let mut c = AsyncTransportConfiguration::new(FooResponseHandler);
+    c.set_read_timeout(Duration::from_millis(100));
+    let transport =
+        BalancedTransport::<_, TokioSleep, _>::new(connectors, c, BalanceStrategy::RoundRobin);
+
+    // The server does not reply to the frames starting with `T`.
+    let interaction = transport.create_interaction(c"MyInteraction")?;
+    let err = interaction
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("Tabcd"),
+            Default::default(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<IoError>().map(|err| err.kind()),
+        Some(IoErrorKind::TimedOut)
+    );
+    assert_eq!(transport.healthy_endpoints_len(), 1);
+
+    let out = transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("abcde"));
+
+    for server in servers {
+        server.abort();
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn interaction_lost_on_reconnect() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let frames = Arc::new(Mutex::new(vec![]));
+    let server = server(listener, frames.clone());
+
+    let transport = AsyncTransport::<_, TokioSleep, _>::with_connector(
+        Arc::new(tcp_connector(addr)),
+        AsyncTransportConfiguration::new(FooResponseHandler),
+    )
+    .await?;
+
+    let interaction = transport.create_interaction(c"MyInteraction")?;
+    interaction
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+
+    transport.reconnect().await?;
+
+    let err = interaction
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("fghij"),
+            Default::default(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Interaction lost, its connection was replaced"
+    );
+
+    // Nor is it terminated on the new connection.
+    drop(interaction);
+    transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("klmno"),
+            Default::default(),
+        )
+        .await?;
+
+    assert_eq!(
+        *frames.lock().expect(""),
+        vec![Bytes::from("abcde"), Bytes::from("klmno")]
+    );
+
+    server.abort();
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+#![cfg(feature = "impl_tokio")]
+
+use core::{ffi::CStr, time::Duration};
+use std::io::Cursor;
+
+use bytes::{Bytes, BytesMut};
+use fbthrift::{ClientStreamElement, Framing, FramingDecoded, FramingEncodedFinal, Transport};
+use fbthrift_transport::{
+    impl_tokio::TokioSleep, limit::OverloadError, LimitConfiguration, LimitTransport,
+};
+use futures_util::{
+    future::BoxFuture,
+    stream::{self, BoxStream},
+    StreamExt as _,
+};
+
+struct FooTransport;
+
+impl Framing for FooTransport {
+    type EncBuf = BytesMut;
+    type DecBuf = Cursor<Bytes>;
+
+    fn enc_with_capacity(cap: usize) -> Self::EncBuf {
+        BytesMut::with_capacity(cap)
+    }
+}
+
+impl Transport for FooTransport {
+    type RpcOptions = ();
+
+    fn call(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        _rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<'static, anyhow::Result<FramingDecoded<Self>>> {
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(Cursor::new(req))
+        })
+    }
+
+    // Resolves right away, the stream ends once polled.
+    fn call_stream(
+        &self,
+        _service_name: &'static CStr,
+        _fn_name: &'static CStr,
+        req: FramingEncodedFinal<Self>,
+        _rpc_options: Self::RpcOptions,
+    ) -> BoxFuture<
+        'static,
+        anyhow::Result<(
+            FramingDecoded<Self>,
+            BoxStream<'static, anyhow::Result<ClientStreamElement<FramingDecoded<Self>>>>,
+        )>,
+    > {
+        Box::pin(async move { Ok((Cursor::new(req), stream::empty().boxed())) })
+    }
+
+    fn create_interaction(&self, _method_name: &'static CStr) -> Result<Self, anyhow::Error> {
+        Ok(Self)
+    }
+}
+
+fn overload_error(ret: anyhow::Result<Cursor<Bytes>>) -> Option<OverloadError> {
+    ret.err()
+        .and_then(|err| err.downcast_ref::<OverloadError>().copied())
+}
+
+#[tokio::test]
+async fn max_in_flight() {
+    let mut c = LimitConfiguration::new();
+    c.set_max_in_flight(1);
+    let transport = LimitTransport::<_, TokioSleep>::new(FooTransport, c);
+
+    let (ret_1, ret_2) = tokio::join!(
+        transport.call(c"my_service", c"my_fn", Bytes::from("foo"), ()),
+        transport.call(c"my_service", c"my_fn", Bytes::from("bar"), ()),
+    );
+    assert_eq!(ret_1.unwrap().into_inner(), Bytes::from("foo"));
+    assert_eq!(overload_error(ret_2), Some(OverloadError::TooManyInFlight));
+
+    // Permit released.
+    assert!(transport
+        .call(c"my_service", c"my_fn", Bytes::from("foo"), ())
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn max_in_flight_with_queue() {
+    let mut c = LimitConfiguration::new();
+    c.set_max_in_flight(1);
+    c.set_max_queue(1);
+    c.set_queue_timeout(Duration::from_millis(500));
+    let transport = LimitTransport::<_, TokioSleep>::new(FooTransport, c);
+
+    let (ret_1, ret_2, ret_3) = tokio::join!(
+        transport.call(c"my_service", c"my_fn", Bytes::from("foo"), ()),
+        transport.call(c"my_service", c"my_fn", Bytes::from("bar"), ()),
+        transport.call(c"my_service", c"my_fn", Bytes::from("baz"), ()),
+    );
+    assert!(ret_1.is_ok());
+    assert_eq!(ret_2.unwrap().into_inner(), Bytes::from("bar"));
+    assert_eq!(overload_error(ret_3), Some(OverloadError::QueueFull));
+    assert_eq!(transport.queued(), 0);
+
+    //
+    let mut c = LimitConfiguration::new();
+    c.set_max_in_flight(1);
+    c.set_max_queue(1);
+    c.set_queue_timeout(Duration::from_millis(50));
+    let transport = LimitTransport::<_, TokioSleep>::new(FooTransport, c);
+
+    let (ret_1, ret_2) = tokio::join!(
+        transport.call(c"my_service", c"my_fn", Bytes::from("foo"), ()),
+        transport.call(c"my_service", c"my_fn", Bytes::from("bar"), ()),
+    );
+    assert!(ret_1.is_ok());
+    assert_eq!(overload_error(ret_2), Some(OverloadError::QueueTimeout));
+}
+
+#[tokio::test]
+async fn rate_limit() {
+    let mut c = LimitConfiguration::new();
+    c.set_rate_limit(1.0, 1);
+    let transport = LimitTransport::<_, TokioSleep>::new(FooTransport, c);
+
+    assert!(transport
+        .call(c"my_service", c"my_fn", Bytes::from("foo"), ())
+        .await
+        .is_ok());
+    let ret = transport
+        .call(c"my_service", c"my_fn", Bytes::from("foo"), ())
+        .await;
+    assert_eq!(overload_error(ret), Some(OverloadError::RateLimited));
+
+    //
+    let mut c = LimitConfiguration::new();
+    c.set_rate_limit(10.0, 1);
+    c.set_max_queue(1);
+    let transport = LimitTransport::<_, TokioSleep>::new(FooTransport, c);
+
+    let (ret_1, ret_2) = tokio::join!(
+        transport.call(c"my_service", c"my_fn", Bytes::from("foo"), ()),
+        transport.call(c"my_service", c"my_fn", Bytes::from("bar"), ()),
+    );
+    assert!(ret_1.is_ok());
+    assert!(ret_2.is_ok());
+}
+
+#[tokio::test]
+async fn rate_limit_token_given_back_on_drop() {
+    let mut c = LimitConfiguration::new();
+    c.set_rate_limit(1.0, 1);
+    c.set_max_queue(1);
+    c.set_queue_timeout(Duration::from_millis(1500));
+    let transport = LimitTransport::<_, TokioSleep>::new(FooTransport, c);
+
+    assert!(transport
+        .call(c"my_service", c"my_fn", Bytes::from("foo"), ())
+        .await
+        .is_ok());
+    // Dropped while waiting for its token.
+    assert!(tokio::time::timeout(
+        Duration::from_millis(50),
+        transport.call(c"my_service", c"my_fn", Bytes::from("bar"), ()),
+    )
+    .await
+    .is_err());
+
+    // Waits for one token only.
+    assert!(transport
+        .call(c"my_service", c"my_fn", Bytes::from("baz"), ())
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn call_stream_and_interaction() {
+    let mut c = LimitConfiguration::new();
+    c.set_max_in_flight(1);
+    let transport = LimitTransport::<_, TokioSleep>::new(FooTransport, c);
+    let interaction = transport.create_interaction(c"MyInteraction").expect("");
+
+    let (res, stream) = transport
+        .call_stream(c"my_service", c"my_fn", Bytes::from("foo"), ())
+        .await
+        .expect("");
+    assert_eq!(res.into_inner(), Bytes::from("foo"));
+
+    // The open stream holds the permit, shared with the interaction.
+    let ret = interaction
+        .call(c"my_service", c"my_fn", Bytes::from("bar"), ())
+        .await;
+    assert_eq!(overload_error(ret), Some(OverloadError::TooManyInFlight));
+
+    drop(stream);
+    assert!(interaction
+        .call(c"my_service", c"my_fn", Bytes::from("bar"), ())
+        .await
+        .is_ok());
+}
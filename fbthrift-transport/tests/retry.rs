@@ -0,0 +1,155 @@
+#![cfg(feature = "impl_tokio")]
+
+use core::time::Duration;
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{
+    fbthrift_transport_response_handler::MockResponseHandler,
+    impl_tokio::{tcp_connector, TokioSleep},
+    AsyncTransport, AsyncTransportConfiguration, Idempotency, RetryPolicy, RetryTransport,
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpListener,
+};
+
+#[tokio::test]
+async fn retry_idempotent_fn_with_reconnect() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let server = tokio::spawn(async move {
+        // The first connection never replies.
+        let (_stream_1, _) = listener.accept().await?;
+        let (mut stream_2, _) = listener.accept().await?;
+
+        let mut buf = vec![0; 5];
+        stream_2.read_exact(&mut buf).await?;
+        stream_2.write_all(&buf).await?;
+
+        Result::<_, IoError>::Ok(())
+    });
+
+    let mut c = AsyncTransportConfiguration::new(MockResponseHandler);
+    c.set_read_timeout(Duration::from_millis(100));
+    let transport =
+        AsyncTransport::<_, TokioSleep, _>::with_connector(Arc::new(tcp_connector(addr)), c)
+            .await?;
+
+    let mut policy = RetryPolicy::new();
+    policy.set_initial_backoff(Duration::from_millis(10));
+    let mut idempotency = Idempotency::new();
+    idempotency.add_idempotent_fn("my_service", "get");
+    policy.set_idempotency(idempotency);
+    let transport = RetryTransport::new(transport, policy);
+
+    let out = transport
+        .call(
+            c"my_service",
+            c"get",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("abcde"));
+
+    server.await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_retry_non_idempotent_fn() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        drop(stream);
+
+        Result::<_, IoError>::Ok(())
+    });
+
+    let mut c = AsyncTransportConfiguration::new(MockResponseHandler);
+    c.set_read_timeout(Duration::from_millis(100));
+    let transport =
+        AsyncTransport::<_, TokioSleep, _>::with_connector(Arc::new(tcp_connector(addr)), c)
+            .await?;
+    let transport = RetryTransport::new(transport, RetryPolicy::new());
+
+    let err = transport
+        .call(
+            c"my_service",
+            c"set",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await
+        .expect_err("");
+    assert_eq!(
+        err.downcast_ref::<IoError>().map(|err| err.kind()),
+        Some(IoErrorKind::TimedOut)
+    );
+
+    server.await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_retry_without_connector() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await?;
+        let mut buf = [0; 20];
+        let mut n = 0;
+        while let Ok(ret) =
+            tokio::time::timeout(Duration::from_millis(500), stream.read(&mut buf[n..])).await
+        {
+            match ret? {
+                0 => break,
+                m => n += m,
+            }
+        }
+
+        Result::<_, IoError>::Ok(n)
+    });
+
+    let mut c = AsyncTransportConfiguration::new(MockResponseHandler);
+    c.set_read_timeout(Duration::from_millis(100));
+    let transport = AsyncTransport::with_tokio_tcp_connect(addr, c).await?;
+
+    let mut policy = RetryPolicy::new();
+    policy.set_initial_backoff(Duration::from_millis(10));
+    let mut idempotency = Idempotency::new();
+    idempotency.add_idempotent_fn("my_service", "get");
+    policy.set_idempotency(idempotency);
+    let transport = RetryTransport::new(transport, policy);
+
+    let err = transport
+        .call(
+            c"my_service",
+            c"get",
+            Bytes::from("abcde"),
+            Default::default(),
+        )
+        .await
+        .expect_err("");
+    assert_eq!(
+        err.downcast_ref::<IoError>().map(|err| err.kind()),
+        Some(IoErrorKind::TimedOut)
+    );
+
+    // Only the first attempt was written.
+    assert_eq!(server.await??, 5);
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+#![cfg(all(feature = "tracing", feature = "impl_tokio"))]
+
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    collections::HashMap,
+    io::Error as IoError,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bytes::Bytes;
+use fbthrift::Transport as _;
+use fbthrift_transport::{impl_tokio::TokioSleep, AsyncTransport, AsyncTransportConfiguration};
+use fbthrift_transport_response_handler::{
+    CallContext, ResponseHandlerV2, ResponseParseOutcome, StreamElementKind,
+};
+use futures_util::{
+    io::{AsyncRead, AsyncWrite},
+    StreamExt as _,
+};
+use tracing::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+// Reads `chunks` one at a time, discards what is written.
+struct Duplex {
+    chunks: Vec<Vec<u8>>,
+}
+
+impl AsyncRead for Duplex {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let chunks = &mut self.get_mut().chunks;
+        if chunks.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let chunk = &mut chunks[0];
+        let n = buf.len().min(chunk.len());
+        buf[..n].copy_from_slice(&chunk[..n]);
+        chunk.drain(..n);
+        if chunk.is_empty() {
+            chunks.remove(0);
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for Duplex {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Elements end with `;`, the stream ends with `end;`.
+#[derive(Clone)]
+struct FooResponseHandler;
+
+impl ResponseHandlerV2 for FooResponseHandler {
+    fn poll_static_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+    ) -> Poll<Result<Option<Vec<u8>>, IoError>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn poll_parse_response_bytes(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _ctx: &CallContext<'_>,
+        response_bytes: &[u8],
+        _new_bytes_from: usize,
+    ) -> Poll<Result<ResponseParseOutcome, IoError>> {
+        Poll::Ready(Ok(match response_bytes.iter().position(|b| *b == b';') {
+            Some(i) => ResponseParseOutcome::Complete(i + 1),
+            None => ResponseParseOutcome::NeedMore(1),
+        }))
+    }
+
+    fn stream_element_kind(
+        &mut self,
+        _ctx: &CallContext<'_>,
+        element_bytes: &[u8],
+    ) -> Result<StreamElementKind, IoError> {
+        Ok(match element_bytes {
+            b"end;" => StreamElementKind::End,
+            _ => StreamElementKind::Reply,
+        })
+    }
+}
+
+// Event name, name of the span it was emitted in.
+type Events = Arc<Mutex<Vec<(&'static str, Option<&'static str>)>>>;
+
+// Records every event with the span it was emitted in.
+#[derive(Default)]
+struct FooSubscriber {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, &'static str>>,
+    entered: Mutex<Vec<u64>>,
+    events: Events,
+}
+
+impl Subscriber for FooSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        self.spans
+            .lock()
+            .expect("")
+            .insert(id, span.metadata().name());
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let span = self
+            .entered
+            .lock()
+            .expect("")
+            .last()
+            .and_then(|id| self.spans.lock().expect("").get(id).copied());
+        self.events
+            .lock()
+            .expect("")
+            .push((event.metadata().name(), span));
+    }
+
+    fn enter(&self, span: &Id) {
+        self.entered.lock().expect("").push(span.into_u64());
+    }
+
+    fn exit(&self, span: &Id) {
+        let mut entered = self.entered.lock().expect("");
+        if let Some(i) = entered.iter().rposition(|id| *id == span.into_u64()) {
+            entered.remove(i);
+        }
+    }
+}
+
+#[tokio::test]
+async fn events_in_call_span() -> Result<(), Box<dyn std::error::Error>> {
+    let subscriber = FooSubscriber::default();
+    let events = subscriber.events.clone();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let transport = AsyncTransport::<_, TokioSleep, _>::new(
+        Duplex {
+            chunks: vec![b"abc;".to_vec(), b"init;".to_vec(), b"a;end;".to_vec()],
+        },
+        AsyncTransportConfiguration::new(FooResponseHandler),
+    );
+
+    let out = transport
+        .call(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("req;"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(out.into_inner(), Bytes::from("abc;"));
+    let n = events.lock().expect("").len();
+    assert!(n > 0);
+
+    let (res, mut stream) = transport
+        .open_stream(
+            c"my_service",
+            c"my_fn",
+            Bytes::from("req;"),
+            Default::default(),
+        )
+        .await?;
+    assert_eq!(res.into_inner(), Bytes::from("init;"));
+    let m = events.lock().expect("").len();
+    assert!(m > n);
+
+    while let Some(element) = stream.next().await {
+        element?;
+    }
+    // The stream elements are read in the span too.
+    assert!(events.lock().expect("").len() > m);
+
+    for (name, span) in events.lock().expect("").iter() {
+        assert_eq!(*span, Some("fbthrift_transport_call"), "{name}");
+    }
+
+    Ok(())
+}
@@ -2,13 +2,7 @@
 
 #[cfg(test)]
 mod transport_impl_async_io_tests {
-    use core::ffi::CStr;
-    use std::{
-        io::{Error as IoError, ErrorKind as IoErrorKind},
-        net::TcpListener,
-        sync::Arc,
-        thread,
-    };
+    use std::{io::Error as IoError, net::TcpListener, sync::Arc, thread};
 
     use bytes::Bytes;
     use fbthrift::Transport as _;
@@ -25,6 +19,7 @@ mod transport_impl_async_io_tests {
         AsyncTransport, AsyncTransportConfiguration,
     };
 
+    #[allow(dead_code)]
     #[derive(Clone)]
     pub struct FooResponseHandler;
 
@@ -95,13 +90,13 @@ mod transport_impl_async_io_tests {
                 for n in 0..10_usize {
                     let cursor = transport
                         .call(
-                            CStr::from_bytes_with_nul(b"my_service\0").expect(""),
-                            CStr::from_bytes_with_nul(b"my_fn\0").expect(""),
+                            c"my_service",
+                            c"my_fn",
                             Bytes::from("abcde"),
                             Default::default(),
                         )
                         .await
-                        .map_err(|err| IoError::new(IoErrorKind::Other, err))?;
+                        .map_err(IoError::other)?;
 
                     println!("futures_io transport.call {n} {cursor:?}");
                     assert_eq!(cursor.into_inner(), Bytes::from("abcde"));
@@ -130,3 +125,11 @@ fn block_on<T>(future: impl core::future::Future<Output = T>) -> T {
 #[cfg(test)]
 #[path = "./inner_tests/transport_call_future.rs"]
 mod transport_impl_async_io_call_tests;
+
+#[cfg(test)]
+#[path = "./inner_tests/transport_interceptor.rs"]
+mod transport_impl_async_io_interceptor_tests;
+
+#[cfg(test)]
+#[path = "./inner_tests/transport_stream.rs"]
+mod transport_impl_async_io_stream_tests;
@@ -2,8 +2,7 @@
 
 #[cfg(test)]
 mod transport_impl_tokio_tests {
-    use core::ffi::CStr;
-    use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+    use std::io::Error as IoError;
 
     use bytes::Bytes;
     use fbthrift::Transport as _;
@@ -20,6 +19,7 @@ mod transport_impl_tokio_tests {
         AsyncTransport, AsyncTransportConfiguration,
     };
 
+    #[allow(dead_code)]
     #[derive(Clone)]
     pub struct FooResponseHandler;
 
@@ -86,13 +86,13 @@ mod transport_impl_tokio_tests {
             for n in 0..10_usize {
                 let cursor = transport
                     .call(
-                        CStr::from_bytes_with_nul(b"my_service\0").expect(""),
-                        CStr::from_bytes_with_nul(b"my_fn\0").expect(""),
+                        c"my_service",
+                        c"my_fn",
                         Bytes::from("abcde"),
                         Default::default(),
                     )
                     .await
-                    .map_err(|err| IoError::new(IoErrorKind::Other, err))?;
+                    .map_err(IoError::other)?;
 
                 println!("transport_impl_tokio transport.call {n} {cursor:?}");
 
@@ -115,6 +115,69 @@ mod transport_impl_tokio_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn concurrent_calls() -> Result<(), Box<dyn std::error::Error>> {
+        let rt = Runtime::new().unwrap();
+
+        let listener: Result<TcpListener, IoError> =
+            rt.block_on(async move { TcpListener::bind("127.0.0.1:0").await });
+
+        let listener = listener?;
+
+        let listen_addr_for_client = listener.local_addr()?;
+
+        let server: JoinHandle<Result<(), IoError>> = rt.spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+
+            let mut buf = vec![0; 5];
+            for _ in 0..3 {
+                stream.read_exact(&mut buf).await?;
+                tokio::time::sleep(core::time::Duration::from_millis(20)).await;
+                stream.write_all(&buf).await?;
+            }
+
+            Ok(())
+        });
+
+        rt.block_on(async move {
+            let transport = AsyncTransport::with_tokio_tcp_connect(
+                listen_addr_for_client,
+                AsyncTransportConfiguration::new(MockResponseHandler),
+            )
+            .await
+            .expect("");
+
+            // Run one after the other on the connection, each reading its own response.
+            let (ret_1, ret_2, ret_3) = tokio::join!(
+                transport.call(
+                    c"my_service",
+                    c"my_fn",
+                    Bytes::from("abcde"),
+                    Default::default()
+                ),
+                transport.call(
+                    c"my_service",
+                    c"my_fn",
+                    Bytes::from("fghij"),
+                    Default::default()
+                ),
+                transport.call(
+                    c"my_service",
+                    c"my_fn",
+                    Bytes::from("klmno"),
+                    Default::default()
+                ),
+            );
+            assert_eq!(ret_1.expect("").into_inner(), Bytes::from("abcde"));
+            assert_eq!(ret_2.expect("").into_inner(), Bytes::from("fghij"));
+            assert_eq!(ret_3.expect("").into_inner(), Bytes::from("klmno"));
+
+            assert!(server.await.ok().is_some());
+        });
+
+        Ok(())
+    }
 }
 
 //
@@ -130,3 +193,11 @@ fn block_on<T>(future: impl core::future::Future<Output = T>) -> T {
 #[cfg(test)]
 #[path = "./inner_tests/transport_call_future.rs"]
 mod transport_impl_tokio_call_tests;
+
+#[cfg(test)]
+#[path = "./inner_tests/transport_interceptor.rs"]
+mod transport_impl_tokio_interceptor_tests;
+
+#[cfg(test)]
+#[path = "./inner_tests/transport_stream.rs"]
+mod transport_impl_tokio_stream_tests;